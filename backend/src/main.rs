@@ -2,10 +2,17 @@ mod models;
 mod routes;
 mod util;
 
-use crate::util::database::connect_db;
+use crate::util::body_logging::BodyLogging;
+use crate::util::database::{connect_db, DatabaseConnectionConfig, RetryConfig};
+use crate::util::encoding::{set_pretty_json, ErrorEncoding};
+use crate::util::json::{json_config, DEFAULT_JSON_LIMIT};
+use crate::util::load_shedding::LoadShedding;
+use crate::util::request_id::RequestIds;
+use crate::util::security_headers::security_headers;
+use crate::util::server_timing::ServerTiming;
 use actix_web::{web::Data, App, HttpServer};
-use routes::health_check::health_check;
-use routes::messages::messages_scope;
+use routes::routes;
+use routes::version::version_info;
 use sqlx::MySqlPool;
 use util::environment;
 
@@ -14,6 +21,7 @@ type Error = Box<dyn std::error::Error>;
 #[derive(Clone)]
 struct AppState {
     pool: MySqlPool,
+    db_retry: RetryConfig,
 }
 
 #[actix_web::main]
@@ -21,16 +29,65 @@ async fn main() -> Result<(), Error> {
     let config = environment::init().await?;
 
     let app_state = AppState {
-        pool: connect_db(&config.db.url, None).await?,
+        pool: connect_db(
+            config.db.url.expose(),
+            DatabaseConnectionConfig {
+                max_connections: config.db.max_connections,
+                acquire_timeout: config.db.acquire_timeout,
+            },
+        )
+        .await?,
+        db_retry: config.db.retry,
     };
 
+    set_pretty_json(config.server.pretty_json);
+
+    let security_headers_config = config.security_headers.clone();
+    let server_config = &config.server;
+    let json_content_types = server_config.json_content_types.clone();
+    let version_info = version_info(&config.env);
+    let load_shedding = LoadShedding::new(server_config.max_in_flight);
+    let body_logging = BodyLogging {
+        enabled: server_config.log_bodies,
+    };
+    let server_timing = ServerTiming {
+        enabled: server_config.server_timing,
+    };
+
+    log::info!(
+        "Starting {} server at {} (workers: {}, keep-alive: {:?}, client request timeout: {:?}, backlog: {}, max in-flight requests: {}, database connections: {})",
+        config.env,
+        server_config.url,
+        server_config.workers,
+        server_config.keep_alive,
+        server_config.client_request_timeout,
+        server_config.backlog,
+        server_config
+            .max_in_flight
+            .map_or("unlimited".into(), |max_in_flight| max_in_flight.to_string()),
+        config.db.max_connections
+    );
+
     HttpServer::new(move || {
+        // The last middleware wrapped is the outermost. Security headers are outermost so that they are also set on
+        // responses from the other middleware, such as requests shed by LoadShedding.
         App::new()
+            .wrap(server_timing)
+            .wrap(load_shedding.clone())
+            .wrap(ErrorEncoding)
+            .wrap(body_logging)
+            .wrap(RequestIds)
+            .wrap(security_headers(&security_headers_config))
             .app_data(Data::new(app_state.clone()))
-            .service(health_check)
-            .configure(messages_scope)
+            .configure(json_config(&json_content_types, DEFAULT_JSON_LIMIT))
+            .app_data(Data::new(version_info.clone()))
+            .configure(routes(&json_content_types))
     })
-    .bind((config.server.url.host, config.server.url.port))?
+    .keep_alive(server_config.keep_alive)
+    .client_request_timeout(server_config.client_request_timeout)
+    .backlog(server_config.backlog)
+    .workers(server_config.workers)
+    .bind((server_config.url.host.as_str(), server_config.url.port))?
     .run()
     .await?;
 
@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+// Model representing the body of an error response.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub message: String,
+    // Id of the request, matching the X-Request-Id header, so that users can quote it when reporting a problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ErrorResponse {
+            code,
+            message: message.into(),
+            request_id: None,
+        }
+    }
+}
+
+// Machine readable code identifying the kind of error, serialized in snake_case (e.g. `method_not_allowed`).
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    NotFound,
+    Internal,
+    MethodNotAllowed,
+    InvalidListParams,
+    UnsupportedMediaType,
+    InvalidBody,
+    PayloadTooLarge,
+    DatabaseBusy,
+    Overloaded,
+}
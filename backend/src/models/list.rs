@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+// Model representing a page of results returned from a list endpoint.
+#[derive(Serialize, Deserialize)]
+pub struct ListResponse<T> {
+    pub data: Vec<T>,
+    pub meta: ListMeta,
+}
+
+// Model representing the metadata returned alongside a page of results.
+#[derive(Serialize, Deserialize)]
+pub struct ListMeta {
+    pub limit: u32,
+    pub offset: u32,
+    // Number of rows in the page which couldn't be read from the database and were left out of `data`.
+    pub skipped: u32,
+}
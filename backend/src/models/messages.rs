@@ -8,7 +8,7 @@ pub struct Message {
 }
 
 // Model representing the value returned from querying a message from the database.
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, sqlx::FromRow)]
 pub struct DbMessage {
     pub id: Option<String>,
     pub content: String,
@@ -27,8 +27,14 @@ impl From<DbMessage> for Message {
     }
 }
 
-// Model representing the data sent from the frontend to the server.
+// The most bytes a message's content can have, which is the most a TEXT column can hold. Content is checked against this
+// before it's inserted, so that it is rejected with a 400 rather than failing in the database.
+pub const MAX_CONTENT_LENGTH: usize = 65_535;
+
+// Model representing the data sent from the frontend to the server. Unknown fields are rejected so that a typo in a
+// field name isn't silently ignored.
 #[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct NewMessage {
     pub content: String,
 }
@@ -1 +1,4 @@
+pub mod errors;
+pub mod list;
 pub mod messages;
+pub mod version;
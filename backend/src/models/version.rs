@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+// Model representing the build of the server which is running, returned from `GET /version`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VersionResponse {
+    pub version: String,
+    pub commit: String,
+    pub build_timestamp: String,
+    pub environment: String,
+}
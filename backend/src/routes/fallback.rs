@@ -0,0 +1,204 @@
+use crate::util::errors::AppError;
+use actix_web::{dev::ResourceDef, http::Method, HttpRequest, HttpResponse};
+use once_cell::sync::Lazy;
+
+// Every path served by the API along with the methods it accepts. This is used to respond to a known path requested with
+// an unsupported method, and `test_routes_are_served` checks it against the registered routes.
+const ROUTES: &[(&str, &[Method])] = &[
+    ("/health-check", &[Method::GET]),
+    ("/messages", &[Method::GET, Method::POST]),
+    ("/messages/{id}", &[Method::GET]),
+    ("/version", &[Method::GET]),
+];
+
+// ROUTES with each path parsed, which for dynamic paths compiles a regex, so it is only done once.
+static ROUTE_DEFS: Lazy<Vec<(ResourceDef, &[Method])>> = Lazy::new(|| {
+    ROUTES
+        .iter()
+        .map(|(path, methods)| (ResourceDef::new(*path), *methods))
+        .collect()
+});
+
+// Default service for requests which don't match any route. Returns 405 with an Allow header if the path is known but
+// the method isn't supported, otherwise 404.
+pub async fn default_service(req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let allowed = ROUTE_DEFS
+        .iter()
+        .find(|(def, _)| def.is_match(req.path()))
+        .map(|(_, methods)| methods);
+
+    match allowed {
+        Some(methods) if !methods.contains(req.method()) => {
+            let allow = methods
+                .iter()
+                .map(Method::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            Err(AppError::MethodNotAllowed {
+                method: req.method().to_string(),
+                path: req.path().into(),
+                allow,
+            })
+        }
+        _ => Err(AppError::NotFound),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::errors::*,
+        routes::{messages::messages_scope, routes},
+        util::encoding::ErrorEncoding,
+    };
+    use actix_web::{
+        http::{header, StatusCode},
+        test, web, App,
+    };
+
+    #[actix_web::test]
+    async fn test_method_not_allowed() {
+        let app = test::init_service(App::new().default_service(web::to(default_service))).await;
+
+        let req = test::TestRequest::delete()
+            .uri("/messages/0e0b1c6e-7a4c-4a4a-9a0e-9a4f0f5b5d0a")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers().get(header::ALLOW).unwrap(), "GET");
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::MethodNotAllowed);
+    }
+
+    #[actix_web::test]
+    async fn test_allow_lists_every_method() {
+        let app = test::init_service(App::new().default_service(web::to(default_service))).await;
+
+        let req = test::TestRequest::put().uri("/messages").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(res.headers().get(header::ALLOW).unwrap(), "GET, POST");
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_path_not_found() {
+        let app = test::init_service(App::new().default_service(web::to(default_service))).await;
+
+        let req = test::TestRequest::get().uri("/unknown").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+    }
+
+    // Requests every standard method for every path against the real routes. A method listed in ROUTES must be served,
+    // so a 404 or 405 means ROUTES is out of date. Any other method must get a 405, so a 405 missing means a route isn't
+    // listed. No app data is given, so the handlers fail before touching the database.
+    #[actix_web::test]
+    async fn test_routes_are_served() {
+        let app =
+            test::init_service(App::new().configure(routes(&["application/json".into()]))).await;
+
+        for (path, methods) in ROUTES {
+            let uri = path.replace("{id}", "0e0b1c6e-7a4c-4a4a-9a0e-9a4f0f5b5d0a");
+
+            for method in [
+                Method::GET,
+                Method::HEAD,
+                Method::POST,
+                Method::PUT,
+                Method::DELETE,
+                Method::CONNECT,
+                Method::OPTIONS,
+                Method::TRACE,
+                Method::PATCH,
+            ] {
+                let req = test::TestRequest::default()
+                    .method(method.clone())
+                    .uri(&uri)
+                    .to_request();
+                let res = test::call_service(&app, req).await;
+
+                if methods.contains(&method) {
+                    assert_ne!(res.status(), StatusCode::NOT_FOUND, "{} {}", method, path);
+                    assert_ne!(
+                        res.status(),
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "{} {}",
+                        method,
+                        path
+                    );
+                } else {
+                    assert_eq!(
+                        res.status(),
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "{} {}",
+                        method,
+                        path
+                    );
+                }
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_falls_through_messages_scope() {
+        let app = test::init_service(
+            App::new()
+                .configure(messages_scope(&["application/json".into()]))
+                .default_service(web::to(default_service)),
+        )
+        .await;
+
+        let req = test::TestRequest::put().uri("/messages").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[actix_web::test]
+    async fn test_services_after_messages_scope_are_reached() {
+        let app = test::init_service(
+            App::new()
+                .configure(messages_scope(&["application/json".into()]))
+                .route("/after", web::get().to(HttpResponse::Ok))
+                .default_service(web::to(default_service)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/after").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_method_not_allowed_msgpack() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ErrorEncoding)
+                .default_service(web::to(default_service)),
+        )
+        .await;
+
+        let req = test::TestRequest::put()
+            .uri("/messages")
+            .insert_header((header::ACCEPT, "application/msgpack"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(
+            res.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/msgpack"
+        );
+        assert_eq!(res.headers().get(header::VARY).unwrap(), "Accept");
+
+        let body: ErrorResponse = rmp_serde::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(body.code, ErrorCode::MethodNotAllowed);
+    }
+}
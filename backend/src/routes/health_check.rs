@@ -1,9 +1,14 @@
-use crate::AppState;
+use crate::{
+    util::{cache_control::CachePolicy, server_timing::Timings},
+    AppState,
+};
 use actix_web::{get, web, HttpResponse, Responder};
 
-#[get("/health-check")]
-async fn health_check(app_state: web::Data<AppState>) -> impl Responder {
-    let result = sqlx::query("SELECT 1").execute(&app_state.pool).await;
+#[get("/health-check", wrap = "CachePolicy::NoCache")]
+async fn health_check(app_state: web::Data<AppState>, timings: Timings) -> impl Responder {
+    let result = timings
+        .time("db", sqlx::query("SELECT 1").execute(&app_state.pool))
+        .await;
 
     if result.is_ok() {
         return HttpResponse::Ok().finish();
@@ -15,26 +20,26 @@ async fn health_check(app_state: web::Data<AppState>) -> impl Responder {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{util::tests::TestPool, Error};
-    use actix_web::{http::StatusCode, test, web::Data, App};
+    use crate::{
+        util::tests::{test_app, TestPool},
+        Error,
+    };
+    use actix_web::{http::StatusCode, test};
 
     #[actix_web::test]
     async fn test_health_check() -> Result<(), Error> {
         let pool = TestPool::connect().await?;
 
-        let app_state = AppState { pool: pool.get() };
-
-        let app = test::init_service(
-            App::new()
-                .app_data(Data::new(app_state.clone()))
-                .service(health_check),
-        )
-        .await;
+        let app = test_app!(pool.app_state(), |cfg| {
+            cfg.service(health_check);
+        });
 
         let req = test::TestRequest::get().uri("/health-check").to_request();
         let res = test::call_service(&app, req).await;
 
+        // Probes expect 200 rather than 204, so this stays 200 with an empty body.
         assert_eq!(res.status(), StatusCode::OK);
+        assert!(test::read_body(res).await.is_empty());
 
         Ok(())
     }
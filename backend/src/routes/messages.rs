@@ -1,154 +1,536 @@
-use crate::{models::messages::*, AppState};
-use actix_web::{get, post, web, HttpResponse, Responder};
-
-pub fn messages_scope(cfg: &mut web::ServiceConfig) {
-    cfg.service(get_messages)
-        .service(get_message)
-        .service(add_message);
+use crate::{
+    models::{list::*, messages::*},
+    util::{
+        cache_control::CachePolicy,
+        database::{new_id, with_retry},
+        encoding::{EncodedResponse, Encoding},
+        errors::AppError,
+        json::{json_config, Json},
+        list::{list_query_config, map_rows, ListConfig, ListParams, ListQuery, SortOrder},
+        prefer::{Prefer, ReturnPreference},
+        server_timing::Timings,
+    },
+    AppState,
+};
+use actix_web::{
+    get,
+    http::header::{LINK, LOCATION},
+    post, web, HttpRequest, HttpResponse,
+};
+use sqlx::{
+    mysql::{MySql, MySqlArguments, MySqlRow},
+    query::Map,
+    FromRow,
+};
+
+// Message bodies are limited to the most content the column can hold, plus room for the rest of the JSON. A byte of
+// content can take up to 6 bytes once escaped, e.g. `\u0001`, so content within the column limit is never rejected
+// for the size of its body, however much of it is escaped.
+const MESSAGES_JSON_LIMIT: usize = 6 * MAX_CONTENT_LENGTH + 1024;
+
+// The routes are wrapped in a scope so that the JSON and query configs only apply to them. Requests under `/messages`
+// which don't match any of them still fall through to the app's default service.
+pub fn messages_scope(json_content_types: &[String]) -> impl FnOnce(&mut web::ServiceConfig) + '_ {
+    move |cfg| {
+        cfg.service(
+            web::scope("/messages")
+                .configure(json_config(json_content_types, MESSAGES_JSON_LIMIT))
+                .app_data(list_query_config())
+                .service(get_messages)
+                .service(get_message)
+                .service(add_message),
+        );
+    }
 }
 
-#[get("/messages")]
-async fn get_messages(app_state: web::Data<AppState>) -> impl Responder {
-    let messages: sqlx::Result<Vec<Message>> = sqlx::query_as!(
-        DbMessage,
-        "SELECT BIN_TO_UUID(id, true) as id, content FROM messages ORDER BY id"
-    )
-    .fetch_all(&app_state.pool)
-    .await
-    .map(|db_messages| {
-        db_messages
-            .iter()
-            .map(|db_message| db_message.clone().into())
-            .collect()
+// Columns are qualified with the table name so they don't resolve to the formatted values of the same name in the
+// select list. Content can't be sorted or filtered by, as it isn't indexed and any client could make every list request
+// scan the whole table. Every allowed sort and filter is checked to use an index by `test_get_messages_uses_index`.
+const MESSAGES_LIST: ListConfig = ListConfig {
+    sort_columns: &[("id", "messages.id")],
+    filter_columns: &[],
+    tie_breaker: "messages.id",
+    default_sort: "id",
+    default_order: SortOrder::Asc,
+    default_limit: 50,
+    max_limit: 100,
+};
+
+#[get("", wrap = "CachePolicy::NoStore")]
+async fn get_messages(
+    req: HttpRequest,
+    app_state: web::Data<AppState>,
+    params: web::Query<ListParams>,
+    encoding: Encoding,
+    timings: Timings,
+) -> Result<HttpResponse, AppError> {
+    let list_query = params
+        .validate(&MESSAGES_LIST)
+        .map_err(AppError::InvalidListParams)?;
+
+    let sql = list_messages_sql(&list_query);
+
+    // Rows are mapped individually so that a single bad row doesn't fail the whole list.
+    let rows = timings
+        .time(
+            "db",
+            list_query
+                .bind(sqlx::query(&sql))
+                .fetch_all(&app_state.pool),
+        )
+        .await?;
+
+    // Based on the rows fetched rather than those mapped, so a full page with skipped rows still links to the next one.
+    let links = list_query.links(req.path(), req.query_string(), rows.len());
+    let (messages, skipped) = map_rows(rows, |row| {
+        let db_message = DbMessage::from_row(row)?;
+
+        Ok(Message {
+            id: db_message.id.ok_or("Message ID is NULL")?,
+            content: db_message.content,
+        })
     });
 
-    match messages {
-        Ok(messages) => HttpResponse::Ok().json(messages),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    let mut builder = HttpResponse::Ok();
+    if let Some(links) = links {
+        builder.insert_header((LINK, links));
     }
+
+    Ok(builder.encoded(
+        encoding,
+        ListResponse {
+            data: messages,
+            meta: ListMeta {
+                limit: list_query.limit,
+                offset: list_query.offset,
+                skipped,
+            },
+        },
+    ))
+}
+
+fn list_messages_sql(list_query: &ListQuery) -> String {
+    format!(
+        "SELECT BIN_TO_UUID(id, true) as id, content FROM messages{}",
+        list_query.sql()
+    )
+}
+
+#[get("/{id}", wrap = "CachePolicy::NoStore")]
+async fn get_message(
+    app_state: web::Data<AppState>,
+    id: web::Path<String>,
+    encoding: Encoding,
+    timings: Timings,
+) -> Result<HttpResponse, AppError> {
+    let message: Message = timings
+        .time(
+            "db",
+            select_message(id.into_inner()).fetch_optional(&app_state.pool),
+        )
+        .await?
+        .ok_or(AppError::NotFound)?
+        .into();
+
+    Ok(HttpResponse::Ok().encoded(encoding, message))
 }
 
-#[get("/messages/{id}")]
-async fn get_message(app_state: web::Data<AppState>, id: web::Path<String>) -> impl Responder {
-    let message: sqlx::Result<Option<Message>> = sqlx::query_as!(
+// The query is built separately from get_message so that its plan can be checked in tests.
+fn select_message(
+    id: String,
+) -> Map<
+    'static,
+    MySql,
+    impl FnMut(MySqlRow) -> Result<DbMessage, sqlx::Error> + Send,
+    MySqlArguments,
+> {
+    sqlx::query_as!(
         DbMessage,
         "SELECT BIN_TO_UUID(id, true) as id, content FROM messages WHERE id = UUID_TO_BIN(?, true)",
-        id.into_inner()
+        id
     )
-    .fetch_optional(&app_state.pool)
-    .await
-    .map(|db_message| db_message.map(|db_message| db_message.into()));
-
-    match message {
-        Ok(Some(message)) => HttpResponse::Ok().json(message),
-        Ok(None) => HttpResponse::NotFound().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
-    }
 }
 
-#[post("/messages")]
+// Returns the created message unless the client sends `Prefer: return=minimal`. The id is generated before the insert
+// rather than by the column default, so that it can be returned and used in the Location header.
+#[post("", wrap = "CachePolicy::NoStore")]
 async fn add_message(
     app_state: web::Data<AppState>,
-    new_message: web::Json<NewMessage>,
-) -> impl Responder {
-    let result = sqlx::query!(
-        "INSERT INTO messages (content) VALUES (?)",
-        new_message.content
-    )
-    .execute(&app_state.pool)
-    .await;
-
-    match result {
-        Ok(_) => HttpResponse::Created().finish(),
-        Err(_) => HttpResponse::InternalServerError().finish(),
+    new_message: Json<NewMessage>,
+    encoding: Encoding,
+    prefer: Prefer,
+    timings: Timings,
+) -> Result<HttpResponse, AppError> {
+    if new_message.content.len() > MAX_CONTENT_LENGTH {
+        return Err(AppError::InvalidBody(format!(
+            "content must be at most {} bytes",
+            MAX_CONTENT_LENGTH
+        )));
     }
+
+    let id = timings.time("db", new_id(&app_state.pool)).await?;
+
+    timings
+        .time(
+            "db",
+            with_retry(app_state.db_retry, || {
+                sqlx::query!(
+                    "INSERT INTO messages (id, content) VALUES (UUID_TO_BIN(?, true), ?)",
+                    id,
+                    new_message.content
+                )
+                .execute(&app_state.pool)
+            }),
+        )
+        .await?;
+
+    let mut builder = HttpResponse::Created();
+    builder.insert_header((LOCATION, format!("/messages/{}", id)));
+    prefer.applied(&mut builder);
+
+    Ok(match prefer.return_preference {
+        ReturnPreference::Minimal => builder.finish(),
+        ReturnPreference::Representation => builder.encoded(
+            encoding,
+            Message {
+                id,
+                content: new_message.into_inner().content,
+            },
+        ),
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{util::tests::TestPool, Error};
-    use actix_web::{http::StatusCode, test, web::Data, App};
-    use sqlx::mysql;
+    use crate::{
+        models::errors::*,
+        util::{
+            prefer::{PREFER, PREFERENCE_APPLIED},
+            tests::{assert_uses_index, test_app, TestMessage, TestPool},
+        },
+        Error,
+    };
+    use actix_web::{
+        http::{header, StatusCode},
+        test,
+    };
+    use sqlx::{mysql::MySqlPoolOptions, Execute, Row};
+    use std::time::Duration;
     use uuid::Uuid;
 
     #[actix_web::test]
     async fn test_get_messages() -> Result<(), Error> {
         let pool = TestPool::connect().await?;
 
-        let app_state = AppState { pool: pool.get() };
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
 
-        let app = test::init_service(
-            App::new()
-                .app_data(Data::new(app_state.clone()))
-                .service(get_messages),
-        )
-        .await;
+        TestMessage::default().insert(&pool).await?;
 
         let req = test::TestRequest::get().uri("/messages").to_request();
         let res = test::call_service(&app, req).await;
 
         assert_eq!(res.status(), StatusCode::OK);
 
+        let body: ListResponse<Message> = test::read_body_json(res).await;
+        assert!(!body.data.is_empty());
+        assert_eq!(body.meta.skipped, 0);
+
         Ok(())
     }
 
     #[actix_web::test]
-    async fn test_get_message() -> Result<(), Error> {
+    async fn test_get_messages_links() -> Result<(), Error> {
         let pool = TestPool::connect().await?;
 
-        let app_state = AppState { pool: pool.get() };
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
 
-        let app = test::init_service(
-            App::new()
-                .app_data(Data::new(app_state.clone()))
-                .service(get_message),
-        )
-        .await;
+        TestMessage::default().insert(&pool).await?;
+        TestMessage::default().insert(&pool).await?;
 
-        let message_id = Uuid::new_v4().to_string();
-        let new_message = NewMessage {
-            content: "Test message".into(),
+        let req = test::TestRequest::get()
+            .uri("/messages?limit=1&foo=bar")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(
+            res.headers().get(header::LINK).unwrap(),
+            "</messages?limit=1&foo=bar&offset=1>; rel=\"next\""
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/messages?limit=1&offset=1")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        let link = res.headers().get(header::LINK).unwrap().to_str()?;
+        assert!(link.contains("</messages?limit=1&offset=0>; rel=\"prev\""));
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_messages_pages_are_stable() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
+
+        // Ids are time-based, so the seeded messages are next to each other in id order however much data is in the
+        // database. Paging starts from where they are.
+        let mut seeded = Vec::new();
+        for _ in 0..3 {
+            seeded.push(TestMessage::default().insert(&pool).await?.id);
+        }
+        let start: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM messages WHERE id < UUID_TO_BIN(?, true)")
+                .bind(&seeded[0])
+                .fetch_one(&pool.pool)
+                .await?;
+
+        // Page through them one at a time, so each one falls across a page boundary.
+        let mut seen = Vec::new();
+        for offset in start..start + seeded.len() as i64 {
+            let req = test::TestRequest::get()
+                .uri(&format!("/messages?limit=1&offset={}", offset))
+                .to_request();
+            let body: ListResponse<Message> = test::call_and_read_body_json(&app, req).await;
+
+            seen.extend(body.data.into_iter().map(|message| message.id));
+        }
+
+        // The default sort follows creation order.
+        assert_eq!(seen, seeded);
+
+        Ok(())
+    }
+
+    // MESSAGES_LIST only sorts by the unique id, so this uses a test-only config which sorts and filters by content to
+    // check that rows which tie on the sort column are paged in tie-breaker order. An indexed column wouldn't test
+    // anything, as InnoDB returns ties from a secondary index in primary key order anyway. Sorting by content needs a
+    // filesort, which can return ties in a different order on every page without the tie-breaker.
+    #[actix_web::test]
+    async fn test_list_ties_are_broken() -> Result<(), Error> {
+        const TIED_LIST: ListConfig = ListConfig {
+            sort_columns: &[("content", "messages.content")],
+            filter_columns: &[("content", "messages.content")],
+            ..MESSAGES_LIST
         };
 
-        let result: sqlx::Result<mysql::MySqlQueryResult> = sqlx::query!(
-            "INSERT INTO messages (id, content) VALUES (UUID_TO_BIN(?, true), ?)",
-            message_id,
-            new_message.content
-        )
-        .execute(&app_state.pool)
-        .await;
+        let pool = TestPool::connect().await?;
+
+        let content = format!("Tied message {}", Uuid::new_v4());
+        let mut seeded = Vec::new();
+        for _ in 0..3 {
+            seeded.push(
+                TestMessage::default()
+                    .content(&content)
+                    .insert(&pool)
+                    .await?
+                    .id,
+            );
+        }
+
+        // Page through them one at a time, so each tie falls across a page boundary.
+        let mut seen = Vec::new();
+        for offset in 0..seeded.len() as u32 {
+            let list_query = ListParams {
+                limit: Some(1),
+                offset: Some(offset),
+                sort: Some("content".into()),
+                order: None,
+                filters: vec![("content".into(), content.clone())],
+            }
+            .validate(&TIED_LIST)
+            .unwrap();
+            let sql = list_messages_sql(&list_query);
+
+            for row in list_query
+                .bind(sqlx::query(&sql))
+                .fetch_all(&pool.pool)
+                .await?
+            {
+                seen.push(row.try_get::<String, _>("id")?);
+            }
+        }
+
+        // The tie-breaker is the id, which follows creation order.
+        assert_eq!(seen, seeded);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_messages_invalid_filter() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
+
+        // Content isn't indexed, so filtering by it would scan the whole table.
+        let req = test::TestRequest::get()
+            .uri("/messages?filter%5Bcontent%5D=Hello")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::InvalidListParams);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_messages_invalid_sort() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
 
-        if result.is_ok() {
+        // Content isn't indexed, so sorting by it would scan the whole table.
+        for sort in ["password", "content"] {
             let req = test::TestRequest::get()
-                .uri(&format!("/messages/{}", message_id))
+                .uri(&format!("/messages?sort={}", sort))
                 .to_request();
             let res = test::call_service(&app, req).await;
 
-            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "sort {}", sort);
+
+            let body: ErrorResponse = test::read_body_json(res).await;
+            assert_eq!(body.code, ErrorCode::InvalidListParams);
+        }
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_messages_non_numeric_limit() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/messages?limit=abc")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::InvalidListParams);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_messages_uses_index() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        TestMessage::default().insert(&pool).await?;
+
+        // Every allowed sort in both orders, and every allowed filter, so a column added to MESSAGES_LIST without an
+        // index fails here.
+        let default = || ListParams {
+            limit: None,
+            offset: None,
+            sort: None,
+            order: None,
+            filters: Vec::new(),
+        };
+        let mut cases = Vec::new();
+        for (sort, _) in MESSAGES_LIST.sort_columns {
+            for order in [SortOrder::Asc, SortOrder::Desc] {
+                cases.push(ListParams {
+                    sort: Some(sort.to_string()),
+                    order: Some(order),
+                    ..default()
+                });
+            }
+        }
+        for (filter, _) in MESSAGES_LIST.filter_columns {
+            cases.push(ListParams {
+                filters: vec![(filter.to_string(), "Test message".into())],
+                ..default()
+            });
+        }
 
-            let message: Message = test::read_body_json(res).await;
-            assert_eq!(message.id, message_id);
-            assert_eq!(message.content, new_message.content);
+        for params in cases {
+            let list_query = params.validate(&MESSAGES_LIST).unwrap();
+            let sql = format!("EXPLAIN {}", list_messages_sql(&list_query));
 
-            return Ok(());
+            let plans = pool.explain(list_query.bind(sqlx::query(&sql))).await?;
+            assert_uses_index(&plans, &sql);
         }
 
-        Err("Failed to insert value into DB".into())
+        Ok(())
     }
 
     #[actix_web::test]
-    async fn test_get_message_not_found() -> Result<(), Error> {
+    async fn test_get_message_uses_index() -> Result<(), Error> {
         let pool = TestPool::connect().await?;
 
-        let app_state = AppState { pool: pool.get() };
+        // The id must exist, otherwise MySQL reports "no matching row in const table" without a key.
+        let seeded = TestMessage::default().insert(&pool).await?;
 
-        let app = test::init_service(
-            App::new()
-                .app_data(Data::new(app_state.clone()))
-                .service(get_message),
-        )
-        .await;
+        let sql = format!("EXPLAIN {}", select_message(seeded.id.clone()).sql());
+        let query = sqlx::query(&sql).bind(&seeded.id);
+
+        let plans = pool.explain(query).await?;
+        assert_uses_index(&plans, &sql);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_message() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
+
+        let seeded = TestMessage::default()
+            .content("Seeded message")
+            .insert(&pool)
+            .await?;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/messages/{}", seeded.id))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let message: Message = test::read_body_json(res).await;
+        assert_eq!(message.id, seeded.id);
+        assert_eq!(message.content, seeded.content);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_get_message_not_found() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
 
         let non_existent_id = Uuid::new_v4().to_string();
         let req = test::TestRequest::get()
@@ -158,21 +540,56 @@ mod tests {
 
         assert_eq!(res.status(), StatusCode::NOT_FOUND);
 
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::NotFound);
+
         Ok(())
     }
 
     #[actix_web::test]
-    async fn test_add_message() -> Result<(), Error> {
+    async fn test_pool_exhausted() -> Result<(), Error> {
         let pool = TestPool::connect().await?;
 
-        let app_state = AppState { pool: pool.get() };
+        let app_state = pool.app_state();
+        let app_state = AppState {
+            pool: MySqlPoolOptions::new()
+                .max_connections(1)
+                .acquire_timeout(Duration::from_millis(50))
+                .connect_with((*app_state.pool.connect_options()).clone())
+                .await?,
+            ..app_state
+        };
 
-        let app = test::init_service(
-            App::new()
-                .app_data(Data::new(app_state.clone()))
-                .service(add_message),
-        )
-        .await;
+        let app = test_app!(
+            app_state.clone(),
+            messages_scope(&["application/json".into()])
+        );
+
+        // Hold the only connection so the handler can't get one.
+        let _connection = app_state.pool.acquire().await?;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/messages/{}", Uuid::new_v4()))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(res.headers().contains_key(header::RETRY_AFTER));
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::DatabaseBusy);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_add_message() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
 
         let new_message = NewMessage {
             content: "Test message".into(),
@@ -185,6 +602,88 @@ mod tests {
         let res = test::call_service(&app, req).await;
 
         assert_eq!(res.status(), StatusCode::CREATED);
+        assert!(!res.headers().contains_key(PREFERENCE_APPLIED));
+
+        // A create returns the resource it created, which is also what its Location serves.
+        let location = res.headers().get(header::LOCATION).unwrap().clone();
+        let message: Message = test::read_body_json(res).await;
+        assert_eq!(location, format!("/messages/{}", message.id).as_str());
+        assert_eq!(message.content, new_message.content);
+
+        // Ids are time-based, like the column default, so they are stored in creation order.
+        assert_eq!(Uuid::parse_str(&message.id)?.get_version_num(), 1);
+
+        let req = test::TestRequest::get()
+            .uri(location.to_str()?)
+            .to_request();
+        let created: Message = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(created.id, message.id);
+        assert_eq!(created.content, message.content);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_add_message_minimal() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/messages")
+            .insert_header((PREFER, "return=minimal"))
+            .set_json(&NewMessage {
+                content: "Test message".into(),
+            })
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert!(res.headers().contains_key(header::LOCATION));
+        assert_eq!(
+            res.headers().get(PREFERENCE_APPLIED).unwrap(),
+            "return=minimal"
+        );
+        assert!(test::read_body(res).await.is_empty());
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_add_message_content_length() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+
+        let app = test_app!(
+            pool.app_state(),
+            messages_scope(&["application/json".into()])
+        );
+
+        // Content at the limit is accepted however much of it has to be escaped in the body.
+        for content in ["a", "\n", "\"", "\u{1}"] {
+            let req = test::TestRequest::post()
+                .uri("/messages")
+                .set_json(&NewMessage {
+                    content: content.repeat(MAX_CONTENT_LENGTH),
+                })
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), StatusCode::CREATED, "content {:?}", content);
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/messages")
+            .set_json(&NewMessage {
+                content: "a".repeat(MAX_CONTENT_LENGTH + 1),
+            })
+            .to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::InvalidBody);
 
         Ok(())
     }
@@ -1,2 +1,18 @@
+pub mod fallback;
 pub mod health_check;
 pub mod messages;
+pub mod version;
+
+use actix_web::web;
+use fallback::default_service;
+use messages::messages_scope;
+
+// Registers every route, along with the default service for requests which don't match any of them.
+pub fn routes(json_content_types: &[String]) -> impl FnOnce(&mut web::ServiceConfig) + '_ {
+    move |cfg| {
+        cfg.service(health_check::health_check)
+            .service(version::version)
+            .configure(messages_scope(json_content_types))
+            .default_service(web::to(default_service));
+    }
+}
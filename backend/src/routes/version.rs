@@ -0,0 +1,51 @@
+use crate::{
+    models::version::VersionResponse,
+    util::{
+        cache_control::CachePolicy,
+        encoding::{EncodedResponse, Encoding},
+        environment::Environment,
+    },
+};
+use actix_web::{get, web, HttpResponse, Responder};
+
+// Version information for this build, given to the app as app data. The commit and build timestamp are set by the
+// build script.
+pub fn version_info(environment: &Environment) -> VersionResponse {
+    VersionResponse {
+        version: env!("CARGO_PKG_VERSION").into(),
+        commit: env!("BUILD_GIT_COMMIT").into(),
+        build_timestamp: env!("BUILD_TIMESTAMP").into(),
+        environment: environment.to_string(),
+    }
+}
+
+#[get("/version", wrap = "CachePolicy::NoCache")]
+async fn version(version: web::Data<VersionResponse>, encoding: Encoding) -> impl Responder {
+    HttpResponse::Ok().encoded(encoding, version.get_ref())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http::StatusCode, test, web::Data, App};
+
+    #[actix_web::test]
+    async fn test_version() {
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(version_info(&Environment::Production)))
+                .service(version),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/version").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let body: VersionResponse = test::read_body_json(res).await;
+        assert_eq!(body.version, env!("CARGO_PKG_VERSION"));
+        assert!(!body.commit.is_empty());
+        assert_eq!(body.environment, "production");
+    }
+}
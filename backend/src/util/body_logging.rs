@@ -0,0 +1,426 @@
+use super::json::DEFAULT_JSON_LIMIT;
+use actix_web::{
+    body::{BodySize, BoxBody, EitherBody, MessageBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    error::PayloadError,
+    http::{
+        header::{HeaderMap, CONTENT_TYPE},
+        Method,
+    },
+    web::{Bytes, BytesMut},
+    HttpMessage,
+};
+use futures_util::Stream;
+use serde_json::Value;
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+// Fields whose values are never logged. A field is redacted if its name contains any of these, ignoring case, so e.g.
+// `access_token` and `newPassword` are covered too.
+const SENSITIVE_FIELDS: [&str; 5] = ["password", "token", "secret", "salt", "authorization"];
+
+// Maximum length of a logged body. Anything longer is truncated.
+const MAX_LOGGED_BYTES: usize = 4096;
+
+// Maximum length of a body which is copied so it can be logged. A JSON body can only be redacted if it is complete, so
+// longer bodies are logged by length only. This matches the app-wide JSON limit, so logging never holds more of a
+// request than the JSON extractor would.
+const MAX_CAPTURED_BYTES: usize = DEFAULT_JSON_LIMIT;
+
+// Middleware which logs JSON request and response bodies at trace level, for debugging integrations. Bodies are
+// copied as they pass through to the handler or the client, and logged once they have been read in full, so they are
+// never buffered ahead of the reader and streamed responses keep streaming. Only bodies with a JSON content type are
+// logged, as other bodies can't be redacted. Sensitive fields are redacted, and a body which turns out not to be valid
+// JSON is only logged by length. This is only enabled through configuration, and never in production.
+#[derive(Clone, Copy)]
+pub struct BodyLogging {
+    pub enabled: bool,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for BodyLogging
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = BodyLoggingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(BodyLoggingMiddleware {
+            service: Rc::new(service),
+            enabled: self.enabled,
+        }))
+    }
+}
+
+pub struct BodyLoggingMiddleware<S> {
+    service: Rc<S>,
+    enabled: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for BodyLoggingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        if !self.enabled || !log::log_enabled!(log::Level::Trace) {
+            return Box::pin(async move { Ok(service.call(req).await?.map_into_left_body()) });
+        }
+
+        Box::pin(async move {
+            let method = req.method().clone();
+            let path = req.path().to_string();
+
+            if is_json(req.headers()) {
+                let payload = LoggedPayload {
+                    payload: req.take_payload(),
+                    capture: Capture::new("Request", &method, &path),
+                };
+                req.set_payload(Payload::Stream {
+                    payload: Box::pin(payload),
+                });
+            }
+
+            let res = service.call(req).await?;
+
+            if !is_json(res.headers()) {
+                return Ok(res.map_into_left_body());
+            }
+
+            Ok(res.map_body(|_, body| {
+                EitherBody::right(BoxBody::new(LoggedBody {
+                    body: BoxBody::new(body),
+                    capture: Capture::new("Response", &method, &path),
+                }))
+            }))
+        })
+    }
+}
+
+// Copy of a body as it is read, which is logged when the body is dropped, i.e. once it has been read in full or the
+// reader has given up on it. The copy is dropped as soon as the body passes MAX_CAPTURED_BYTES.
+struct Capture {
+    label: &'static str,
+    method: Method,
+    path: String,
+    body: BytesMut,
+    len: usize,
+}
+
+impl Capture {
+    fn new(label: &'static str, method: &Method, path: &str) -> Self {
+        Capture {
+            label,
+            method: method.clone(),
+            path: path.into(),
+            body: BytesMut::new(),
+            len: 0,
+        }
+    }
+
+    fn record(&mut self, chunk: &Bytes) {
+        self.len += chunk.len();
+
+        if self.len <= MAX_CAPTURED_BYTES {
+            self.body.extend_from_slice(chunk);
+        } else {
+            self.body = BytesMut::new();
+        }
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        let logged = match self.len {
+            len if len > MAX_CAPTURED_BYTES => format!("<{} bytes, too long to log>", len),
+            _ => loggable(&self.body),
+        };
+
+        log::trace!(
+            "{} body for {} {}: {}",
+            self.label,
+            self.method,
+            self.path,
+            logged
+        );
+    }
+}
+
+// Request payload which copies each chunk into a Capture as the handler reads it.
+struct LoggedPayload {
+    payload: Payload,
+    capture: Capture,
+}
+
+impl Stream for LoggedPayload {
+    type Item = Result<Bytes, PayloadError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.payload).poll_next(cx);
+
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            this.capture.record(chunk);
+        }
+
+        poll
+    }
+}
+
+// Response body which copies each chunk into a Capture as it is sent to the client.
+struct LoggedBody {
+    body: BoxBody,
+    capture: Capture,
+}
+
+impl MessageBody for LoggedBody {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.body).poll_next(cx);
+
+        if let Poll::Ready(Some(Ok(chunk))) = &poll {
+            this.capture.record(chunk);
+        }
+
+        poll
+    }
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .and_then(|content_type| content_type.split(';').next())
+        .is_some_and(|essence| {
+            let essence = essence.trim().to_ascii_lowercase();
+            essence == "application/json" || essence.ends_with("+json")
+        })
+}
+
+// Renders a body for the log with sensitive fields redacted, truncated to MAX_LOGGED_BYTES.
+fn loggable(body: &[u8]) -> String {
+    let mut value: Value = match serde_json::from_slice(body) {
+        Ok(value) => value,
+        Err(_) => return format!("<{} bytes, not valid JSON>", body.len()),
+    };
+    redact(&mut value);
+
+    let mut logged = value.to_string();
+    if logged.len() > MAX_LOGGED_BYTES {
+        let mut end = MAX_LOGGED_BYTES;
+        while !logged.is_char_boundary(end) {
+            end -= 1;
+        }
+        logged.truncate(end);
+        logged.push_str("...");
+    }
+
+    logged
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, value) in fields.iter_mut() {
+                let name = name.to_ascii_lowercase();
+                if SENSITIVE_FIELDS.iter().any(|field| name.contains(field)) {
+                    *value = Value::String("[REDACTED]".into());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+    use futures_util::stream;
+    use std::sync::{Mutex, Once};
+
+    // Captures log lines, so tests can check what was logged. The logger is global, so tests filter the lines by path.
+    static LOGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    struct TestLogger;
+
+    impl log::Log for TestLogger {
+        fn enabled(&self, _: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            LOGS.lock().unwrap().push(record.args().to_string());
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn init_logger() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            log::set_logger(&TestLogger).unwrap();
+            log::set_max_level(log::LevelFilter::Trace);
+        });
+    }
+
+    #[actix_web::test]
+    async fn test_redacts_sensitive_fields() {
+        let body = Bytes::from(
+            r#"{"content":"Test message","password":"hunter2","user":{"accessToken":"abc"},"items":[{"secret":1}]}"#,
+        );
+
+        assert_eq!(
+            loggable(&body),
+            r#"{"content":"Test message","items":[{"secret":"[REDACTED]"}],"password":"[REDACTED]","user":{"accessToken":"[REDACTED]"}}"#
+        );
+        assert_eq!(
+            loggable(&Bytes::from("password=hunter2")),
+            "<16 bytes, not valid JSON>"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_bodies_pass_through() {
+        init_logger();
+
+        let app = test::init_service(App::new().wrap(BodyLogging { enabled: true }).route(
+            "/body-logging",
+            web::post().to(|body: web::Json<Value>| async move {
+                HttpResponse::Ok().json(body.into_inner())
+            }),
+        ))
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/body-logging")
+            .set_json(serde_json::json!({ "content": "Test message", "password": "hunter2" }))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(
+            body,
+            serde_json::json!({ "content": "Test message", "password": "hunter2" })
+        );
+
+        let logs: Vec<String> = LOGS
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.contains("POST /body-logging"))
+            .cloned()
+            .collect();
+        assert_eq!(
+            logs,
+            vec![
+                r#"Request body for POST /body-logging: {"content":"Test message","password":"[REDACTED]"}"#,
+                r#"Response body for POST /body-logging: {"content":"Test message","password":"[REDACTED]"}"#,
+            ]
+        );
+    }
+
+    fn logs_for(path: &str) -> Vec<String> {
+        LOGS.lock()
+            .unwrap()
+            .iter()
+            .filter(|line| line.contains(path))
+            .cloned()
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn test_long_bodies_logged_by_length() {
+        init_logger();
+
+        let app = test::init_service(App::new().wrap(BodyLogging { enabled: true }).route(
+            "/body-logging-long",
+            web::post().to(|body: web::Json<Value>| async move {
+                HttpResponse::Ok().json(body.into_inner())
+            }),
+        ))
+        .await;
+
+        let content = "a".repeat(MAX_CAPTURED_BYTES);
+        let req = test::TestRequest::post()
+            .uri("/body-logging-long")
+            .set_json(serde_json::json!({ "content": content }))
+            .to_request();
+        let body: Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(body, serde_json::json!({ "content": content }));
+
+        let len = MAX_CAPTURED_BYTES + r#"{"content":""}"#.len();
+        assert_eq!(
+            logs_for("POST /body-logging-long"),
+            vec![
+                format!(
+                    "Request body for POST /body-logging-long: <{} bytes, too long to log>",
+                    len
+                ),
+                format!(
+                    "Response body for POST /body-logging-long: <{} bytes, too long to log>",
+                    len
+                ),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_streamed_response_stays_streamed() {
+        init_logger();
+
+        let app = test::init_service(App::new().wrap(BodyLogging { enabled: true }).route(
+            "/body-logging-stream",
+            web::get().to(|| async {
+                let chunks = [r#"{"data":["#, "1,", "2", "]}"]
+                    .map(|chunk| Ok::<_, actix_web::Error>(Bytes::from(chunk)));
+                HttpResponse::Ok()
+                    .content_type("application/json")
+                    .streaming(stream::iter(chunks))
+            }),
+        ))
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/body-logging-stream")
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.response().body().size(), BodySize::Stream);
+        assert_eq!(test::read_body(res).await, r#"{"data":[1,2]}"#);
+        assert_eq!(
+            logs_for("GET /body-logging-stream"),
+            vec![r#"Response body for GET /body-logging-stream: {"data":[1,2]}"#]
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderValue, CACHE_CONTROL},
+};
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+};
+
+// Named Cache-Control policies which routes opt into using the `wrap` attribute, e.g.
+// `#[get("/messages", wrap = "CachePolicy::NoStore")]`. The header is only set if the handler hasn't already set one.
+#[derive(Clone, Copy)]
+pub enum CachePolicy {
+    // For user data, which must never be stored by the client or any proxy/CDN.
+    NoStore,
+    // For responses which can be stored but must be revalidated before every use.
+    NoCache,
+}
+
+impl CachePolicy {
+    pub fn header_value(&self) -> HeaderValue {
+        HeaderValue::from_static(match self {
+            CachePolicy::NoStore => "no-store",
+            CachePolicy::NoCache => "no-cache",
+        })
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for CachePolicy
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = CachePolicyMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CachePolicyMiddleware {
+            service,
+            policy: *self,
+        }))
+    }
+}
+
+pub struct CachePolicyMiddleware<S> {
+    service: S,
+    policy: CachePolicy,
+}
+
+impl<S, B> Service<ServiceRequest> for CachePolicyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+        let header_value = self.policy.header_value();
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+
+            if !res.headers().contains_key(CACHE_CONTROL) {
+                res.headers_mut().insert(CACHE_CONTROL, header_value);
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_cache_policy_sets_header() {
+        let app = test::init_service(
+            App::new().service(
+                web::resource("/")
+                    .wrap(CachePolicy::NoStore)
+                    .to(HttpResponse::Ok),
+            ),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.headers().get(CACHE_CONTROL).unwrap(), "no-store");
+    }
+
+    #[actix_web::test]
+    async fn test_cache_policy_keeps_handler_header() {
+        let app = test::init_service(App::new().service(
+            web::resource("/").wrap(CachePolicy::NoStore).to(|| async {
+                HttpResponse::Ok()
+                    .insert_header((CACHE_CONTROL, "private"))
+                    .finish()
+            }),
+        ))
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.headers().get(CACHE_CONTROL).unwrap(), "private");
+    }
+}
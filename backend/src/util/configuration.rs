@@ -1,15 +1,63 @@
-use super::{environment::Environment, url::Url};
+use super::{database::RetryConfig, environment::Environment, secrets::Secret, url::Url};
+use actix_web::http::header::HeaderValue;
+use std::time::Duration;
 
 pub struct Configuration {
     pub env: Environment,
     pub db: DatabaseConfiguration,
     pub server: ServerConfiguration,
+    pub security_headers: SecurityHeadersConfiguration,
 }
 
 pub struct DatabaseConfiguration {
-    pub url: String,
+    // The connection string includes the password, so it is a secret.
+    pub url: Secret,
+    pub max_connections: u32,
+    pub acquire_timeout: Duration,
+    pub retry: RetryConfig,
 }
 
 pub struct ServerConfiguration {
     pub url: Url,
+    // Number of worker threads. Defaults to one per physical CPU core, which is actix's default.
+    pub workers: usize,
+    pub keep_alive: Duration,
+    pub client_request_timeout: Duration,
+    pub backlog: u32,
+    // Maximum number of requests handled at once across every worker, after which requests are shed. None is unlimited.
+    pub max_in_flight: Option<usize>,
+    // Content types accepted for JSON request bodies.
+    pub json_content_types: Vec<String>,
+    // Whether to add the Server-Timing header to responses. This exposes internal timings so is off by default.
+    pub server_timing: bool,
+    // Whether to pretty-print JSON responses, for reading them while debugging.
+    pub pretty_json: bool,
+    // Whether to log request and response bodies at trace level. Never enabled in production.
+    pub log_bodies: bool,
+}
+
+// Values for the security headers added to every response. A value of None disables that header.
+#[derive(Clone)]
+pub struct SecurityHeadersConfiguration {
+    pub strict_transport_security: Option<HeaderValue>,
+    pub content_type_options: Option<HeaderValue>,
+    pub frame_options: Option<HeaderValue>,
+    pub content_security_policy: Option<HeaderValue>,
+    pub referrer_policy: Option<HeaderValue>,
+}
+
+impl Default for SecurityHeadersConfiguration {
+    fn default() -> Self {
+        SecurityHeadersConfiguration {
+            strict_transport_security: Some(HeaderValue::from_static(
+                "max-age=31536000; includeSubDomains",
+            )),
+            content_type_options: Some(HeaderValue::from_static("nosniff")),
+            frame_options: Some(HeaderValue::from_static("DENY")),
+            content_security_policy: Some(HeaderValue::from_static(
+                "default-src 'none'; frame-ancestors 'none'",
+            )),
+            referrer_policy: Some(HeaderValue::from_static("no-referrer")),
+        }
+    }
 }
@@ -1,8 +1,21 @@
 use crate::Error;
-use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
+use actix_web::rt::time::sleep;
+use rand::Rng;
+use sqlx::{
+    mysql::{MySqlDatabaseError, MySqlPoolOptions},
+    MySqlPool,
+};
+use std::{future::Future, time::Duration};
+
+// MySQL error numbers which indicate a transient failure, where retrying the same operation is expected to succeed.
+// 1205: Lock wait timeout exceeded.
+// 1213: Deadlock found when trying to get lock.
+const TRANSIENT_ERROR_NUMBERS: [u16; 2] = [1205, 1213];
 
 pub struct DatabaseConnectionConfig {
     pub max_connections: u32,
+    // How long a request waits for a connection when they are all in use, before failing with PoolTimedOut.
+    pub acquire_timeout: Duration,
 }
 
 pub async fn connect_db<C>(database_url: &str, config: C) -> Result<MySqlPool, Error>
@@ -10,15 +23,253 @@ where
     C: Into<Option<DatabaseConnectionConfig>>,
 {
     let mut max_connections = 10;
+    let mut acquire_timeout = Duration::from_secs(5);
 
     if let Some(config) = config.into() {
         max_connections = config.max_connections;
+        acquire_timeout = config.acquire_timeout;
     }
 
     let pool: MySqlPool = MySqlPoolOptions::new()
         .max_connections(max_connections)
+        .acquire_timeout(acquire_timeout)
         .connect(database_url)
         .await?;
 
     Ok(pool)
 }
+
+// Generates a new id with MySQL's UUID(), the same as the id column defaults, for when the id is needed before the row
+// is inserted. These UUIDs are time-based, which is what `UUID_TO_BIN(?, true)` relies on to store ids in creation
+// order. A random (v4) UUID would scatter inserts across the primary key.
+pub async fn new_id(pool: &MySqlPool) -> sqlx::Result<String> {
+    sqlx::query_scalar!("SELECT UUID() AS `id!`")
+        .fetch_one(pool)
+        .await
+}
+
+// Configuration for retrying database operations which fail with a transient error.
+#[derive(Clone, Copy)]
+pub struct RetryConfig {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    // Longest backoff for a single retry, however many retries have been made.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 3,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+// Returns true if the error is a MySQL error that is safe to retry, such as a deadlock or lock wait timeout. Logical
+// errors (constraint violations, syntax errors, etc.) are never treated as transient.
+pub fn is_transient_error(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(db_error) => db_error
+            .try_downcast_ref::<MySqlDatabaseError>()
+            .is_some_and(|db_error| TRANSIENT_ERROR_NUMBERS.contains(&db_error.number())),
+        _ => false,
+    }
+}
+
+// Runs a database operation, retrying it with jittered exponential backoff if it fails with a transient MySQL error.
+pub async fn with_retry<F, Fut, T>(config: RetryConfig, operation: F) -> sqlx::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = sqlx::Result<T>>,
+{
+    retry_if(config, is_transient_error, operation).await
+}
+
+async fn retry_if<F, Fut, T, E, P>(
+    config: RetryConfig,
+    is_retryable: P,
+    mut operation: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    P: Fn(&E) -> bool,
+{
+    let mut attempt = 0;
+
+    loop {
+        match operation().await {
+            Err(error) if attempt < config.max_retries && is_retryable(&error) => {
+                attempt += 1;
+
+                // Full jitter: wait a random amount of time between zero and the exponential backoff for this attempt.
+                let delay = rand::thread_rng().gen_range(Duration::ZERO..=backoff(config, attempt));
+
+                log::debug!(
+                    "Retrying database operation after transient error (attempt {} of {}, waiting {:?})",
+                    attempt,
+                    config.max_retries,
+                    delay
+                );
+
+                sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+// Exponential backoff for a retry attempt, starting at 1, capped at the configured max delay so that a large number of
+// retries can neither overflow nor keep a request waiting for long.
+fn backoff(config: RetryConfig, attempt: u32) -> Duration {
+    2u32.checked_pow(attempt - 1)
+        .and_then(|factor| config.base_delay.checked_mul(factor))
+        .map_or(config.max_delay, |delay| delay.min(config.max_delay))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::TestPool;
+    use sqlx::Executor;
+    use std::cell::Cell;
+
+    #[derive(Debug, PartialEq)]
+    enum TestError {
+        Transient,
+        Logical,
+    }
+
+    fn test_config(max_retries: u32) -> RetryConfig {
+        RetryConfig {
+            max_retries,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = test_config(100);
+
+        assert_eq!(backoff(config, 1), Duration::from_millis(1));
+        assert_eq!(backoff(config, 3), Duration::from_millis(4));
+        assert_eq!(backoff(config, 5), Duration::from_millis(10));
+        assert_eq!(backoff(config, 40), Duration::from_millis(10));
+        assert_eq!(backoff(config, u32::MAX), Duration::from_millis(10));
+    }
+
+    #[actix_web::test]
+    async fn test_retry_transient_error() {
+        let attempts = Cell::new(0);
+
+        let result = retry_if(
+            test_config(3),
+            |error| *error == TestError::Transient,
+            || {
+                attempts.set(attempts.get() + 1);
+                let attempt = attempts.get();
+                async move {
+                    match attempt {
+                        1 | 2 => Err(TestError::Transient),
+                        _ => Ok(attempt),
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[actix_web::test]
+    async fn test_retry_gives_up_after_max_retries() {
+        let attempts = Cell::new(0);
+
+        let result: Result<(), TestError> = retry_if(
+            test_config(2),
+            |error| *error == TestError::Transient,
+            || {
+                attempts.set(attempts.get() + 1);
+                async { Err(TestError::Transient) }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Err(TestError::Transient));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[actix_web::test]
+    async fn test_no_retry_on_logical_error() {
+        let attempts = Cell::new(0);
+
+        let result: Result<(), TestError> = retry_if(
+            test_config(3),
+            |error| *error == TestError::Transient,
+            || {
+                attempts.set(attempts.get() + 1);
+                async { Err(TestError::Logical) }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Err(TestError::Logical));
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[test]
+    fn test_non_database_error_is_not_transient() {
+        assert!(!is_transient_error(&sqlx::Error::RowNotFound));
+        assert!(!is_transient_error(&sqlx::Error::PoolTimedOut));
+    }
+
+    // The MySQL tests raise errors with SIGNAL, which sets the error number a real deadlock or constraint violation would
+    // have.
+    #[actix_web::test]
+    async fn test_with_retry_mysql_deadlock() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+        let attempts = Cell::new(0);
+
+        with_retry(test_config(3), || {
+            attempts.set(attempts.get() + 1);
+            pool.pool.execute(match attempts.get() {
+                1 => "SIGNAL SQLSTATE '40001' SET MYSQL_ERRNO = 1213",
+                _ => "DO 1",
+            })
+        })
+        .await?;
+
+        assert_eq!(attempts.get(), 2);
+
+        Ok(())
+    }
+
+    #[actix_web::test]
+    async fn test_with_retry_mysql_logical_error() -> Result<(), Error> {
+        let pool = TestPool::connect().await?;
+        let attempts = Cell::new(0);
+
+        let result = with_retry(test_config(3), || {
+            attempts.set(attempts.get() + 1);
+            pool.pool
+                .execute("SIGNAL SQLSTATE '23000' SET MYSQL_ERRNO = 1062")
+        })
+        .await;
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error
+                .as_database_error()
+                .and_then(|error| error.try_downcast_ref::<MySqlDatabaseError>())
+                .map(|error| error.number()),
+            Some(1062)
+        );
+        assert_eq!(attempts.get(), 1);
+
+        Ok(())
+    }
+}
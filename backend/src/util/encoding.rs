@@ -0,0 +1,327 @@
+use super::request_id::RequestId;
+use crate::models::errors::ErrorResponse;
+use actix_web::{
+    body::{BoxBody, EitherBody},
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{self, Header, HeaderMap, HeaderValue, Quality, CONTENT_TYPE, VARY},
+    FromRequest, HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+};
+
+const MSGPACK_MIME_TYPES: [&str; 2] = ["application/msgpack", "application/x-msgpack"];
+
+static PRETTY_JSON: OnceCell<bool> = OnceCell::new();
+
+// Sets whether JSON responses are pretty-printed. This is called once at startup, so JSON is always compact in tests.
+pub fn set_pretty_json(pretty: bool) {
+    PRETTY_JSON.set(pretty).ok();
+}
+
+fn to_json<T: Serialize>(value: &T, pretty: bool) -> serde_json::Result<Vec<u8>> {
+    if pretty {
+        serde_json::to_vec_pretty(value)
+    } else {
+        serde_json::to_vec(value)
+    }
+}
+
+// Encoding used for a response body, negotiated from the request's Accept header. JSON is used unless the client
+// prefers MessagePack.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+}
+
+impl Encoding {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let accept = match header::Accept::parse(req) {
+            Ok(accept) => accept,
+            Err(_) => return Encoding::Json,
+        };
+
+        // Use the first supported type in order of the client's preference. Types with q=0 are ones the client has
+        // marked as unacceptable, so they are never used, even when a wildcard would otherwise match them.
+        let (acceptable, refused): (Vec<_>, Vec<_>) = accept
+            .iter()
+            .cloned()
+            .partition(|mime| mime.quality > Quality::ZERO);
+        let is_refused = |essence: &str| {
+            refused
+                .iter()
+                .any(|mime| mime.item.essence_str() == essence)
+        };
+
+        for mime in header::Accept(acceptable).ranked() {
+            if MSGPACK_MIME_TYPES.contains(&mime.essence_str()) {
+                return Encoding::MessagePack;
+            }
+
+            if mime.subtype() == "json" {
+                return Encoding::Json;
+            }
+
+            // A wildcard matches either encoding, so use the first one the client hasn't refused.
+            if mime.subtype() == "*" && matches!(mime.type_().as_str(), "*" | "application") {
+                if !is_refused("application/json") {
+                    return Encoding::Json;
+                }
+
+                if !MSGPACK_MIME_TYPES.iter().any(|essence| is_refused(essence)) {
+                    return Encoding::MessagePack;
+                }
+            }
+        }
+
+        Encoding::Json
+    }
+
+    // Serializes a value with this encoding, returning the body and its content type.
+    fn encode<T: Serialize>(&self, value: &T) -> Option<(Vec<u8>, &'static str)> {
+        match self {
+            Encoding::Json => to_json(value, *PRETTY_JSON.get().unwrap_or(&false))
+                .ok()
+                .map(|body| (body, "application/json")),
+            Encoding::MessagePack => rmp_serde::to_vec_named(value)
+                .ok()
+                .map(|body| (body, MSGPACK_MIME_TYPES[0])),
+        }
+    }
+}
+
+// Adds `Vary: Accept` to a response whose body was negotiated from the Accept header, so that caches don't serve one
+// encoding to a client which asked for another. Nothing is added if the response already varies on Accept.
+pub fn vary_accept(headers: &mut HeaderMap) {
+    let varies = headers
+        .get_all(VARY)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| name.trim().eq_ignore_ascii_case("accept"));
+
+    if !varies {
+        headers.append(VARY, HeaderValue::from_static("Accept"));
+    }
+}
+
+impl FromRequest for Encoding {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(Encoding::from_request(req)))
+    }
+}
+
+// Extension of HttpResponseBuilder to set the response body using the negotiated encoding, in the same way as `json`.
+pub trait EncodedResponse {
+    fn encoded<T: Serialize>(&mut self, encoding: Encoding, value: T) -> HttpResponse;
+}
+
+impl EncodedResponse for HttpResponseBuilder {
+    fn encoded<T: Serialize>(&mut self, encoding: Encoding, value: T) -> HttpResponse {
+        match encoding.encode(&value) {
+            Some((body, content_type)) => {
+                let mut res = self.insert_header((CONTENT_TYPE, content_type)).body(body);
+                vary_accept(res.headers_mut());
+                res
+            }
+            None => HttpResponse::InternalServerError().finish(),
+        }
+    }
+}
+
+// Middleware which finishes error bodies. AppError builds its response without access to the request, so it always
+// encodes as JSON and stores the ErrorResponse in the response extensions. This adds the request's id and encodes it
+// again with the encoding the client prefers.
+pub struct ErrorEncoding;
+
+impl<S, B> Transform<S, ServiceRequest> for ErrorEncoding
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = ErrorEncodingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ErrorEncodingMiddleware { service }))
+    }
+}
+
+pub struct ErrorEncodingMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for ErrorEncodingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let encoding = Encoding::from_request(req.request());
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await?;
+
+            let error = res.response().extensions().get::<ErrorResponse>().cloned();
+            let mut error = match error {
+                Some(error) => error,
+                None => return Ok(res.map_into_left_body()),
+            };
+            error.request_id = res
+                .request()
+                .extensions()
+                .get::<RequestId>()
+                .map(|request_id| request_id.to_string());
+
+            match encoding.encode(&error) {
+                Some((body, content_type)) => {
+                    let mut res = res.map_body(|_, _| EitherBody::right(BoxBody::new(body)));
+                    res.headers_mut()
+                        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+                    vary_accept(res.headers_mut());
+                    Ok(res)
+                }
+                None => Ok(res.map_into_left_body()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App};
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct TestBody {
+        content: String,
+    }
+
+    fn encoding_for(accept: Option<&str>) -> Encoding {
+        let mut req = test::TestRequest::default();
+
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+
+        Encoding::from_request(&req.to_http_request())
+    }
+
+    #[actix_web::test]
+    async fn test_encoding_negotiation() {
+        assert_eq!(encoding_for(None), Encoding::Json);
+        assert_eq!(encoding_for(Some("*/*")), Encoding::Json);
+        assert_eq!(encoding_for(Some("application/json")), Encoding::Json);
+        assert_eq!(encoding_for(Some("text/html")), Encoding::Json);
+        assert_eq!(
+            encoding_for(Some("application/msgpack")),
+            Encoding::MessagePack
+        );
+        assert_eq!(
+            encoding_for(Some("application/json;q=0.5, application/msgpack")),
+            Encoding::MessagePack
+        );
+        assert_eq!(
+            encoding_for(Some("application/json, application/msgpack;q=0.5")),
+            Encoding::Json
+        );
+        assert_eq!(
+            encoding_for(Some("application/msgpack;q=0")),
+            Encoding::Json
+        );
+        assert_eq!(
+            encoding_for(Some("application/msgpack;q=0, application/json;q=0.1")),
+            Encoding::Json
+        );
+        assert_eq!(
+            encoding_for(Some("application/json;q=0, */*")),
+            Encoding::MessagePack
+        );
+        assert_eq!(
+            encoding_for(Some("application/msgpack;q=0, */*")),
+            Encoding::Json
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_pretty_json() {
+        let body = TestBody {
+            content: "Test message".into(),
+        };
+
+        assert_eq!(
+            to_json(&body, false).unwrap(),
+            br#"{"content":"Test message"}"#
+        );
+        assert_eq!(
+            to_json(&body, true).unwrap(),
+            b"{\n  \"content\": \"Test message\"\n}"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_msgpack_response() {
+        let app = test::init_service(App::new().route(
+            "/",
+            web::get().to(|encoding: Encoding| async move {
+                HttpResponse::Ok().encoded(
+                    encoding,
+                    TestBody {
+                        content: "Test message".into(),
+                    },
+                )
+            }),
+        ))
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/")
+            .insert_header((header::ACCEPT, "application/msgpack"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(
+            res.headers().get(CONTENT_TYPE).unwrap(),
+            "application/msgpack"
+        );
+        assert_eq!(res.headers().get(VARY).unwrap(), "Accept");
+
+        let body: TestBody = rmp_serde::from_slice(&test::read_body(res).await).unwrap();
+        assert_eq!(body.content, "Test message");
+    }
+
+    #[actix_web::test]
+    async fn test_vary_accept() {
+        let mut headers = HeaderMap::new();
+        headers.insert(VARY, HeaderValue::from_static("Origin, accept"));
+        vary_accept(&mut headers);
+        assert_eq!(headers.get_all(VARY).count(), 1);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(VARY, HeaderValue::from_static("Origin"));
+        vary_accept(&mut headers);
+        assert_eq!(
+            headers.get_all(VARY).collect::<Vec<_>>(),
+            vec!["Origin", "Accept"]
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use super::{
-    configuration::{Configuration, DatabaseConfiguration, ServerConfiguration},
+    configuration::{
+        Configuration, DatabaseConfiguration, SecurityHeadersConfiguration, ServerConfiguration,
+    },
+    database::RetryConfig,
+    secrets::secret_provider,
     url::{Url, UrlProtocol},
 };
 use crate::Error;
+use actix_web::http::header::HeaderValue;
 use once_cell::sync::Lazy;
-use std::{env, fmt::Display};
+use std::{env, fmt::Display, time::Duration};
 
 #[derive(Eq, PartialEq)]
 pub enum Environment {
@@ -45,21 +50,63 @@ pub async fn init() -> Result<Configuration, Error> {
     let environment =
         Environment::try_from(env::var("ENVIRONMENT").unwrap_or("development".into()))?;
 
-    match environment {
-        Environment::Development => {
-            env::set_var("RUST_LOG", "debug");
-            static INIT_LOGGER: Lazy<()> = Lazy::new(env_logger::init);
-            let _ = &*INIT_LOGGER;
-
-            dotenv::dotenv()?;
-        }
-        Environment::Production => (),
+    // .env is loaded before anything else is read from the environment, so that every variable can be set there.
+    if environment == Environment::Development {
+        dotenv::dotenv()?;
     }
 
+    // Body logging is only available in development, as bodies can contain personal data.
+    let log_bodies = environment == Environment::Development
+        && env::var("LOG_BODIES").unwrap_or("false".into()).parse()?;
+
+    // Development always logs at debug level. Production keeps any RUST_LOG that is set, and otherwise logs at info
+    // level, so that the startup configuration, server errors and counters are logged.
+    let log_filter = match environment {
+        Environment::Development => match log_bodies {
+            true => "debug,backend::util::body_logging=trace".into(),
+            false => "debug".into(),
+        },
+        Environment::Production => env::var("RUST_LOG").unwrap_or("info".into()),
+    };
+    env::set_var("RUST_LOG", log_filter);
+    static INIT_LOGGER: Lazy<()> = Lazy::new(env_logger::init);
+    let _ = &*INIT_LOGGER;
+
+    let security_headers = security_headers_config(&environment, |key| env::var(key).ok())?;
+    let secrets = secret_provider()?;
+    // JSON responses are pretty-printed by default in development only.
+    let pretty_json = match env::var("JSON_PRETTY") {
+        Ok(value) => value.parse()?,
+        Err(_) => environment == Environment::Development,
+    };
+
     Ok(Configuration {
         env: environment,
         db: DatabaseConfiguration {
-            url: env::var("DATABASE_URL")?,
+            url: secrets.get("DATABASE_URL")?,
+            max_connections: env::var("DATABASE_MAX_CONNECTIONS")
+                .unwrap_or("10".into())
+                .parse()?,
+            acquire_timeout: Duration::from_millis(
+                env::var("DATABASE_ACQUIRE_TIMEOUT_MS")
+                    .unwrap_or("5000".into())
+                    .parse()?,
+            ),
+            retry: RetryConfig {
+                max_retries: env::var("DATABASE_MAX_RETRIES")
+                    .unwrap_or("3".into())
+                    .parse()?,
+                base_delay: Duration::from_millis(
+                    env::var("DATABASE_RETRY_BASE_DELAY_MS")
+                        .unwrap_or("50".into())
+                        .parse()?,
+                ),
+                max_delay: Duration::from_millis(
+                    env::var("DATABASE_RETRY_MAX_DELAY_MS")
+                        .unwrap_or("1000".into())
+                        .parse()?,
+                ),
+            },
         },
         server: ServerConfiguration {
             url: Url {
@@ -68,6 +115,179 @@ pub async fn init() -> Result<Configuration, Error> {
                 port: env::var("SERVER_PORT")?.parse()?,
                 path: vec![].into(),
             },
+            // Defaults to the same count actix uses, so that the value logged at startup is the one in use.
+            workers: match env::var("SERVER_WORKERS") {
+                Ok(workers) => workers.parse()?,
+                Err(_) => num_cpus::get_physical(),
+            },
+            keep_alive: Duration::from_secs(
+                env::var("SERVER_KEEP_ALIVE_SECS")
+                    .unwrap_or("5".into())
+                    .parse()?,
+            ),
+            client_request_timeout: Duration::from_millis(
+                env::var("SERVER_CLIENT_REQUEST_TIMEOUT_MS")
+                    .unwrap_or("5000".into())
+                    .parse()?,
+            ),
+            backlog: env::var("SERVER_BACKLOG")
+                .unwrap_or("1024".into())
+                .parse()?,
+            max_in_flight: match env::var("SERVER_MAX_IN_FLIGHT_REQUESTS") {
+                Ok(max_in_flight) => Some(max_in_flight.parse()?),
+                Err(_) => None,
+            },
+            json_content_types: env::var("JSON_CONTENT_TYPES")
+                .unwrap_or("application/json".into())
+                .split(',')
+                .map(|content_type| content_type.trim().to_ascii_lowercase())
+                .filter(|content_type| !content_type.is_empty())
+                .collect(),
+            server_timing: env::var("SERVER_TIMING_ENABLED")
+                .unwrap_or("false".into())
+                .parse()?,
+            pretty_json,
+            log_bodies,
         },
+        security_headers,
     })
 }
+
+// Security headers are enabled by default in production only, and can be toggled with SECURITY_HEADERS_ENABLED. Each
+// header can be overridden with its own variable, where an empty value disables it. HSTS is never sent in development
+// so that browsers don't pin localhost to HTTPS. Variables are read with `var`, so that tests don't have to set them
+// in the process environment.
+fn security_headers_config(
+    environment: &Environment,
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<SecurityHeadersConfiguration, Error> {
+    let enabled: bool = match var("SECURITY_HEADERS_ENABLED") {
+        Some(value) => value.parse()?,
+        None => *environment == Environment::Production,
+    };
+
+    let defaults = SecurityHeadersConfiguration::default();
+    let header = |key: &str, default: Option<HeaderValue>| -> Result<Option<HeaderValue>, Error> {
+        match var(key) {
+            _ if !enabled => Ok(None),
+            Some(value) if value.is_empty() => Ok(None),
+            Some(value) => Ok(Some(HeaderValue::from_str(&value)?)),
+            None => Ok(default),
+        }
+    };
+
+    Ok(SecurityHeadersConfiguration {
+        strict_transport_security: match environment {
+            Environment::Development => None,
+            Environment::Production => header(
+                "SECURITY_HEADER_STRICT_TRANSPORT_SECURITY",
+                defaults.strict_transport_security,
+            )?,
+        },
+        content_type_options: header(
+            "SECURITY_HEADER_CONTENT_TYPE_OPTIONS",
+            defaults.content_type_options,
+        )?,
+        frame_options: header("SECURITY_HEADER_FRAME_OPTIONS", defaults.frame_options)?,
+        content_security_policy: header(
+            "SECURITY_HEADER_CONTENT_SECURITY_POLICY",
+            defaults.content_security_policy,
+        )?,
+        referrer_policy: header("SECURITY_HEADER_REFERRER_POLICY", defaults.referrer_policy)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config_for(environment: Environment, vars: &[(&str, &str)]) -> SecurityHeadersConfiguration {
+        let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+
+        security_headers_config(&environment, |key| {
+            vars.get(key).map(|value| value.to_string())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_no_hsts_in_development() {
+        let config = config_for(
+            Environment::Development,
+            &[
+                ("SECURITY_HEADERS_ENABLED", "true"),
+                ("SECURITY_HEADER_STRICT_TRANSPORT_SECURITY", "max-age=60"),
+            ],
+        );
+
+        assert_eq!(config.strict_transport_security, None);
+        assert_eq!(
+            config.content_type_options,
+            Some(HeaderValue::from_static("nosniff"))
+        );
+
+        let config = config_for(Environment::Production, &[]);
+        assert_eq!(
+            config.strict_transport_security,
+            SecurityHeadersConfiguration::default().strict_transport_security
+        );
+    }
+
+    #[test]
+    fn test_empty_value_disables_header() {
+        let config = config_for(
+            Environment::Production,
+            &[
+                ("SECURITY_HEADER_FRAME_OPTIONS", ""),
+                ("SECURITY_HEADER_REFERRER_POLICY", "same-origin"),
+            ],
+        );
+
+        assert_eq!(config.frame_options, None);
+        assert_eq!(
+            config.referrer_policy,
+            Some(HeaderValue::from_static("same-origin"))
+        );
+        assert_eq!(
+            config.content_type_options,
+            Some(HeaderValue::from_static("nosniff"))
+        );
+    }
+
+    #[test]
+    fn test_enabled_overrides_default() {
+        let headers = |config: SecurityHeadersConfiguration| {
+            [
+                config.strict_transport_security,
+                config.content_type_options,
+                config.frame_options,
+                config.content_security_policy,
+                config.referrer_policy,
+            ]
+        };
+
+        // Disabled by default in development, and enabled by default in production.
+        assert!(headers(config_for(Environment::Development, &[]))
+            .iter()
+            .all(Option::is_none));
+        assert!(headers(config_for(Environment::Production, &[]))
+            .iter()
+            .all(Option::is_some));
+
+        assert!(headers(config_for(
+            Environment::Production,
+            &[("SECURITY_HEADERS_ENABLED", "false")]
+        ))
+        .iter()
+        .all(Option::is_none));
+        assert_eq!(
+            config_for(
+                Environment::Development,
+                &[("SECURITY_HEADERS_ENABLED", "true")]
+            )
+            .frame_options,
+            Some(HeaderValue::from_static("DENY"))
+        );
+    }
+}
@@ -0,0 +1,180 @@
+use super::{
+    encoding::{EncodedResponse, Encoding},
+    metrics::DATABASE_POOL_TIMEOUTS,
+};
+use crate::models::errors::*;
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+use std::fmt::Display;
+
+// Seconds a client is asked to wait before retrying when the server or database is busy.
+const RETRY_AFTER: u64 = 1;
+
+// Error returned from route handlers, so they can use `?` and return `Result<HttpResponse, AppError>`. Each variant
+// maps to a status code and an ErrorCode, which are returned with an ErrorResponse body.
+#[derive(Debug)]
+pub enum AppError {
+    NotFound,
+    Database(sqlx::Error),
+    // No connection became available from the pool in time. The database is overloaded rather than broken.
+    DatabaseBusy,
+    // Too many requests are already in flight, so this one was shed without being handled.
+    Overloaded,
+    InvalidListParams(String),
+    MethodNotAllowed {
+        method: String,
+        path: String,
+        allow: String,
+    },
+    UnsupportedMediaType(String),
+    InvalidBody(String),
+    PayloadTooLarge(String),
+}
+
+impl AppError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            AppError::NotFound => ErrorCode::NotFound,
+            AppError::Database(_) => ErrorCode::Internal,
+            AppError::DatabaseBusy => ErrorCode::DatabaseBusy,
+            AppError::Overloaded => ErrorCode::Overloaded,
+            AppError::InvalidListParams(_) => ErrorCode::InvalidListParams,
+            AppError::MethodNotAllowed { .. } => ErrorCode::MethodNotAllowed,
+            AppError::UnsupportedMediaType(_) => ErrorCode::UnsupportedMediaType,
+            AppError::InvalidBody(_) => ErrorCode::InvalidBody,
+            AppError::PayloadTooLarge(_) => ErrorCode::PayloadTooLarge,
+        }
+    }
+
+    // Message returned to the client. Internal errors are only described in the log, as they can expose details of the
+    // database.
+    fn message(&self) -> String {
+        match self {
+            AppError::Database(_) => "An internal error occurred".into(),
+            error => error.to_string(),
+        }
+    }
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::NotFound => f.write_str("Not found"),
+            AppError::Database(error) => write!(f, "Database error: {}", error),
+            AppError::DatabaseBusy => f.write_str("The database is busy, please try again later"),
+            AppError::Overloaded => f.write_str("The server is busy, please try again later"),
+            AppError::InvalidListParams(message)
+            | AppError::UnsupportedMediaType(message)
+            | AppError::InvalidBody(message)
+            | AppError::PayloadTooLarge(message) => f.write_str(message),
+            AppError::MethodNotAllowed { method, path, .. } => {
+                write!(f, "Method {} is not allowed for {}", method, path)
+            }
+        }
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::PoolTimedOut => {
+                DATABASE_POOL_TIMEOUTS
+                    .increment(format_args!("Timed out waiting for a database connection"));
+                AppError::DatabaseBusy
+            }
+            error => AppError::Database(error),
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::DatabaseBusy | AppError::Overloaded => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::InvalidListParams(_) => StatusCode::BAD_REQUEST,
+            AppError::MethodNotAllowed { .. } => StatusCode::METHOD_NOT_ALLOWED,
+            AppError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::InvalidBody(_) => StatusCode::BAD_REQUEST,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+
+    // The body is always JSON here, as the request isn't available. The ErrorResponse is also stored in the response
+    // extensions so that the ErrorEncoding middleware can re-encode it for clients which prefer another encoding.
+    fn error_response(&self) -> HttpResponse {
+        let mut builder = HttpResponse::build(self.status_code());
+
+        match self {
+            AppError::MethodNotAllowed { allow, .. } => {
+                builder.insert_header((header::ALLOW, allow.as_str()));
+            }
+            AppError::DatabaseBusy | AppError::Overloaded => {
+                builder.insert_header((header::RETRY_AFTER, RETRY_AFTER));
+            }
+            AppError::Database(_) => log::error!("{}", self),
+            _ => (),
+        }
+
+        let body = ErrorResponse::new(self.code(), self.message());
+        let mut res = builder.encoded(Encoding::Json, &body);
+        res.extensions_mut().insert(body);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::body::to_bytes;
+
+    #[actix_web::test]
+    async fn test_error_body() {
+        let res = AppError::InvalidListParams("Invalid sort".into()).error_response();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body = to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(
+            body,
+            r#"{"code":"invalid_list_params","message":"Invalid sort"}"#
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_internal_error_hides_details() {
+        let res = AppError::Database(sqlx::Error::RowNotFound).error_response();
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body: ErrorResponse =
+            serde_json::from_slice(&to_bytes(res.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body.code, ErrorCode::Internal);
+        assert_eq!(body.message, "An internal error occurred");
+    }
+
+    #[actix_web::test]
+    async fn test_not_found_body() {
+        let res = AppError::NotFound.error_response();
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let body = to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"code":"not_found","message":"Not found"}"#);
+    }
+
+    #[actix_web::test]
+    async fn test_pool_timeout_is_busy() {
+        let res = AppError::from(sqlx::Error::PoolTimedOut).error_response();
+
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(res.headers().get(header::RETRY_AFTER).unwrap(), "1");
+
+        let body: ErrorResponse =
+            serde_json::from_slice(&to_bytes(res.into_body()).await.unwrap()).unwrap();
+        assert_eq!(body.code, ErrorCode::DatabaseBusy);
+    }
+}
@@ -0,0 +1,253 @@
+use super::errors::AppError;
+use actix_web::{
+    dev::Payload,
+    error::JsonPayloadError,
+    web::{self, JsonConfig},
+    FromRequest, HttpMessage, HttpRequest,
+};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use std::ops::Deref;
+
+// Size limit for JSON request bodies, unless a scope sets its own with `json_config`.
+pub const DEFAULT_JSON_LIMIT: usize = 256 * 1024;
+
+// Content types accepted by `Json`, registered by `json_config`.
+struct JsonContentTypes(Vec<String>);
+
+// Builds the config for JSON request bodies. Only the given content types are accepted by `Json`, and a request with a
+// missing or different content type is rejected with a 415 before the body is parsed. Bodies larger than `limit` bytes
+// are rejected with a 413. Scopes can register their own config with a different limit, which replaces the app-wide
+// one.
+pub fn json_config(content_types: &[String], limit: usize) -> impl FnOnce(&mut web::ServiceConfig) {
+    let content_types = content_types.to_vec();
+
+    move |cfg| {
+        cfg.app_data(
+            JsonConfig::default()
+                .limit(limit)
+                .content_type_required(true)
+                .error_handler(json_error_handler),
+        )
+        .app_data(JsonContentTypes(content_types));
+    }
+}
+
+// Extractor for JSON request bodies, to be used in place of `web::Json`. `web::Json` accepts any `*/json` or `*+json`
+// content type whatever the JsonConfig allows, so the content type is checked against the configured list first.
+pub struct Json<T>(pub T);
+
+impl<T> Json<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Json<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let allowed = match req.app_data::<JsonContentTypes>() {
+            Some(JsonContentTypes(content_types)) => match req.mime_type() {
+                Ok(Some(mime)) => content_types
+                    .iter()
+                    .any(|content_type| content_type.eq_ignore_ascii_case(mime.essence_str())),
+                _ => false,
+            },
+            // Without a config, `web::Json` falls back to its own content type check.
+            None => true,
+        };
+
+        if !allowed {
+            let error = json_error_handler(JsonPayloadError::ContentType, req);
+            return Box::pin(async move { Err(error) });
+        }
+
+        let json = web::Json::<T>::from_request(req, payload);
+        Box::pin(async move { Ok(Json(json.await?.into_inner())) })
+    }
+}
+
+fn json_error_handler(err: JsonPayloadError, req: &HttpRequest) -> actix_web::Error {
+    match err {
+        JsonPayloadError::ContentType => {
+            let message = match req.headers().get(actix_web::http::header::CONTENT_TYPE) {
+                Some(content_type) => format!(
+                    "Unsupported content type: {}",
+                    content_type.to_str().unwrap_or("<invalid>")
+                ),
+                None => "Missing content type".into(),
+            };
+
+            AppError::UnsupportedMediaType(message).into()
+        }
+        JsonPayloadError::OverflowKnownLength { limit, .. }
+        | JsonPayloadError::Overflow { limit } => {
+            AppError::PayloadTooLarge(format!("Body must be at most {} bytes", limit)).into()
+        }
+        // serde's message names the offending field, e.g. "unknown field `contnet`, expected `content`".
+        JsonPayloadError::Deserialize(error) => AppError::InvalidBody(error.to_string()).into(),
+        err => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{errors::*, messages::NewMessage};
+    use actix_web::{
+        http::{header::CONTENT_TYPE, StatusCode},
+        test, web, App, HttpResponse,
+    };
+
+    async fn echo(body: Json<serde_json::Value>) -> HttpResponse {
+        HttpResponse::Ok().json(body.into_inner())
+    }
+
+    #[actix_web::test]
+    async fn test_content_type_enforcement() {
+        let app = test::init_service(
+            App::new()
+                .configure(json_config(
+                    &[
+                        "application/json".into(),
+                        "application/vnd.manifold+json".into(),
+                    ],
+                    DEFAULT_JSON_LIMIT,
+                ))
+                .route("/", web::post().to(echo)),
+        )
+        .await;
+
+        for (content_type, status) in [
+            (Some("application/json"), StatusCode::OK),
+            (Some("application/json; charset=utf-8"), StatusCode::OK),
+            (Some("application/vnd.manifold+json"), StatusCode::OK),
+            (Some("text/plain"), StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            // actix's own check accepts any JSON subtype or `+json` suffix, so these check that the list is enforced.
+            (Some("text/json"), StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            (Some("image/foo+json"), StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            (None, StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        ] {
+            let mut req = test::TestRequest::post()
+                .uri("/")
+                .set_payload(r#"{"content":"Test message"}"#);
+
+            if let Some(content_type) = content_type {
+                req = req.insert_header((CONTENT_TYPE, content_type));
+            }
+
+            let res = test::call_service(&app, req.to_request()).await;
+            assert_eq!(res.status(), status, "content type {:?}", content_type);
+
+            if status == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+                let body: ErrorResponse = test::read_body_json(res).await;
+                assert_eq!(body.code, ErrorCode::UnsupportedMediaType);
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_restrictive_content_types() {
+        let app = test::init_service(
+            App::new()
+                .configure(json_config(
+                    &["application/vnd.manifold+json".into()],
+                    DEFAULT_JSON_LIMIT,
+                ))
+                .route("/", web::post().to(echo)),
+        )
+        .await;
+
+        for (content_type, status) in [
+            ("application/vnd.manifold+json", StatusCode::OK),
+            ("application/json", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            ("text/json", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            ("image/foo+json", StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/")
+                .insert_header((CONTENT_TYPE, content_type))
+                .set_payload(r#"{"content":"Test message"}"#)
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), status, "content type {}", content_type);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_unknown_field_rejected() {
+        let app = test::init_service(
+            App::new()
+                .configure(json_config(
+                    &["application/json".into()],
+                    DEFAULT_JSON_LIMIT,
+                ))
+                .route(
+                    "/",
+                    web::post().to(|body: Json<NewMessage>| async move {
+                        HttpResponse::Ok().json(body.into_inner())
+                    }),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .insert_header((CONTENT_TYPE, "application/json"))
+            .set_payload(r#"{"contnet":"Test message"}"#)
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::InvalidBody);
+        assert!(body.message.contains("contnet"), "{}", body.message);
+    }
+
+    #[actix_web::test]
+    async fn test_scope_limit() {
+        let app = test::init_service(
+            App::new()
+                .configure(json_config(
+                    &["application/json".into()],
+                    DEFAULT_JSON_LIMIT,
+                ))
+                .service(
+                    web::scope("/small")
+                        .configure(json_config(&["application/json".into()], 16))
+                        .route("", web::post().to(echo)),
+                )
+                .route("/", web::post().to(echo)),
+        )
+        .await;
+
+        for (uri, status) in [
+            ("/", StatusCode::OK),
+            ("/small", StatusCode::PAYLOAD_TOO_LARGE),
+        ] {
+            let req = test::TestRequest::post()
+                .uri(uri)
+                .insert_header((CONTENT_TYPE, "application/json"))
+                .set_payload(r#"{"content":"Test message"}"#)
+                .to_request();
+            let res = test::call_service(&app, req).await;
+            assert_eq!(res.status(), status, "uri {}", uri);
+
+            if status == StatusCode::PAYLOAD_TOO_LARGE {
+                let body: ErrorResponse = test::read_body_json(res).await;
+                assert_eq!(body.code, ErrorCode::PayloadTooLarge);
+            }
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use super::errors::AppError;
+use crate::Error;
+use actix_web::{error::QueryPayloadError, web::QueryConfig, HttpRequest};
+use serde::{
+    de::{IgnoredAny, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use sqlx::{
+    mysql::{MySql, MySqlArguments},
+    query::Query,
+};
+use std::fmt::{self, Display};
+
+// Query parameters accepted by list endpoints, e.g. `?limit=20&offset=40&sort=content&order=desc`. Filters are given
+// as `filter[<name>]=<value>`, e.g. `?filter[content]=Hello`, and only match rows where the column equals the value.
+pub struct ListParams {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub sort: Option<String>,
+    pub order: Option<SortOrder>,
+    // Pairs of the filter name and value, in the order they were given.
+    pub filters: Vec<(String, String)>,
+}
+
+// Deserialized by hand, as the filter names aren't known up front and `#[serde(flatten)]` can't parse numbers from a
+// query string. Any other parameter is ignored.
+impl<'de> Deserialize<'de> for ListParams {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ListParamsVisitor;
+
+        impl<'de> Visitor<'de> for ListParamsVisitor {
+            type Value = ListParams;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("list parameters")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ListParams, A::Error> {
+                let mut params = ListParams {
+                    limit: None,
+                    offset: None,
+                    sort: None,
+                    order: None,
+                    filters: Vec::new(),
+                };
+
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "limit" => params.limit = Some(map.next_value()?),
+                        "offset" => params.offset = Some(map.next_value()?),
+                        "sort" => params.sort = Some(map.next_value()?),
+                        "order" => params.order = Some(map.next_value()?),
+                        key => match key
+                            .strip_prefix("filter[")
+                            .and_then(|key| key.strip_suffix(']'))
+                        {
+                            Some(name) => params.filters.push((name.into(), map.next_value()?)),
+                            None => {
+                                map.next_value::<IgnoredAny>()?;
+                            }
+                        },
+                    }
+                }
+
+                Ok(params)
+            }
+        }
+
+        deserializer.deserialize_map(ListParamsVisitor)
+    }
+}
+
+// Builds the config for list query strings, so that values which don't parse (e.g. `?limit=abc` or `?order=sideways`)
+// are rejected with the same error as values which fail validation.
+pub fn list_query_config() -> QueryConfig {
+    QueryConfig::default().error_handler(list_query_error_handler)
+}
+
+fn list_query_error_handler(err: QueryPayloadError, _: &HttpRequest) -> actix_web::Error {
+    match err {
+        QueryPayloadError::Deserialize(error) => {
+            AppError::InvalidListParams(format!("Invalid list parameters: {}", error)).into()
+        }
+        err => err.into(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+impl Display for SortOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        })
+    }
+}
+
+// Per-resource list configuration. Only the columns in `sort_columns` can be sorted by and only the columns in
+// `filter_columns` can be filtered by, which is what makes it safe to put them directly into the SQL.
+pub struct ListConfig {
+    // Pairs of the name accepted in the `sort` parameter and the SQL column it maps to.
+    pub sort_columns: &'static [(&'static str, &'static str)],
+    // Pairs of the name accepted in a `filter[<name>]` parameter and the SQL column it maps to.
+    pub filter_columns: &'static [(&'static str, &'static str)],
+    // Unique column appended to every ORDER BY, so rows with equal sort values are always returned in the same order
+    // and pages never overlap or skip rows.
+    pub tie_breaker: &'static str,
+    pub default_sort: &'static str,
+    pub default_order: SortOrder,
+    pub default_limit: u32,
+    pub max_limit: u32,
+}
+
+// Validated list parameters, ready to be applied to a query.
+#[derive(Debug, PartialEq)]
+pub struct ListQuery {
+    // Pairs of the SQL column and the value it must equal.
+    pub filters: Vec<(&'static str, String)>,
+    pub sort_column: &'static str,
+    pub tie_breaker: &'static str,
+    pub order: SortOrder,
+    pub limit: u32,
+    pub offset: u32,
+}
+
+impl ListParams {
+    pub fn validate(&self, config: &ListConfig) -> Result<ListQuery, String> {
+        let sort = self.sort.as_deref().unwrap_or(config.default_sort);
+        let sort_column = config
+            .sort_columns
+            .iter()
+            .find(|(name, _)| *name == sort)
+            .map(|(_, column)| *column)
+            .ok_or_else(|| format!("Sorting by '{}' is not supported", sort))?;
+
+        let filters = self
+            .filters
+            .iter()
+            .map(|(name, value)| {
+                config
+                    .filter_columns
+                    .iter()
+                    .find(|(filter_name, _)| filter_name == name)
+                    .map(|(_, column)| (*column, value.clone()))
+                    .ok_or_else(|| format!("Filtering by '{}' is not supported", name))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let limit = self.limit.unwrap_or(config.default_limit);
+        if limit == 0 || limit > config.max_limit {
+            return Err(format!("Limit must be between 1 and {}", config.max_limit));
+        }
+
+        Ok(ListQuery {
+            filters,
+            sort_column,
+            tie_breaker: config.tie_breaker,
+            order: self.order.unwrap_or(config.default_order),
+            limit,
+            offset: self.offset.unwrap_or(0),
+        })
+    }
+}
+
+impl ListQuery {
+    // SQL to append to the end of a SELECT query without a WHERE clause. The filter values, limit and offset are left
+    // as parameters, which are bound with `bind`.
+    pub fn sql(&self) -> String {
+        let mut sql = String::new();
+
+        for (i, (column, _)) in self.filters.iter().enumerate() {
+            sql.push_str(match i {
+                0 => " WHERE ",
+                _ => " AND ",
+            });
+            sql.push_str(column);
+            sql.push_str(" = ?");
+        }
+
+        if self.sort_column == self.tie_breaker {
+            sql.push_str(&format!(" ORDER BY {} {}", self.sort_column, self.order));
+        } else {
+            sql.push_str(&format!(
+                " ORDER BY {} {}, {} {}",
+                self.sort_column, self.order, self.tie_breaker, self.order
+            ));
+        }
+
+        sql.push_str(" LIMIT ? OFFSET ?");
+        sql
+    }
+
+    // Binds the filter values, limit and offset. This must be called after any other parameters have been bound, as
+    // the fragment from `sql` is at the end of the query.
+    pub fn bind<'q>(
+        &self,
+        query: Query<'q, MySql, MySqlArguments>,
+    ) -> Query<'q, MySql, MySqlArguments> {
+        self.filters
+            .iter()
+            .fold(query, |query, (_, value)| query.bind(value.clone()))
+            .bind(self.limit)
+            .bind(self.offset)
+    }
+
+    // Value for a Link header (RFC 8288) pointing at the pages before and after this one, or None if there are neither.
+    // The links keep the request's other query parameters and only change the offset. They are relative, so they
+    // resolve against whatever host and scheme the client used, including behind a proxy. There is a next page
+    // whenever this one is full, as the total isn't counted.
+    pub fn links(&self, path: &str, query_string: &str, page_len: usize) -> Option<String> {
+        let params = query_string
+            .split('&')
+            .filter(|param| !param.is_empty() && param.split('=').next() != Some("offset"))
+            .collect::<Vec<_>>();
+        let link = |offset: u32, rel: &str| {
+            let mut query = params.clone();
+            let offset = format!("offset={}", offset);
+            query.push(&offset);
+
+            format!("<{}?{}>; rel=\"{}\"", path, query.join("&"), rel)
+        };
+
+        let mut links = Vec::new();
+        if page_len >= self.limit as usize {
+            links.push(link(self.offset.saturating_add(self.limit), "next"));
+        }
+        if self.offset > 0 {
+            links.push(link(self.offset.saturating_sub(self.limit), "prev"));
+        }
+
+        match links.is_empty() {
+            true => None,
+            false => Some(links.join(", ")),
+        }
+    }
+}
+
+// Maps each row of a list query, logging and skipping any row which fails to map rather than failing the whole list.
+// Returns the mapped values and the number of rows skipped.
+pub fn map_rows<R, T, F>(rows: impl IntoIterator<Item = R>, map: F) -> (Vec<T>, u32)
+where
+    F: Fn(&R) -> Result<T, Error>,
+{
+    let mut skipped = 0;
+
+    let values = rows
+        .into_iter()
+        .filter_map(|row| match map(&row) {
+            Ok(value) => Some(value),
+            Err(error) => {
+                log::warn!("Skipping row which failed to map: {}", error);
+                skipped += 1;
+                None
+            }
+        })
+        .collect();
+
+    (values, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::errors::*;
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body, read_body_json, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    const TEST_CONFIG: ListConfig = ListConfig {
+        sort_columns: &[("id", "id"), ("content", "content")],
+        filter_columns: &[("content", "content")],
+        tie_breaker: "id",
+        default_sort: "id",
+        default_order: SortOrder::Asc,
+        default_limit: 20,
+        max_limit: 50,
+    };
+
+    fn params(sort: Option<&str>, limit: Option<u32>) -> ListParams {
+        ListParams {
+            limit,
+            offset: None,
+            sort: sort.map(Into::into),
+            order: None,
+            filters: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_defaults() {
+        let query = params(None, None).validate(&TEST_CONFIG).unwrap();
+
+        assert_eq!(
+            query,
+            ListQuery {
+                filters: Vec::new(),
+                sort_column: "id",
+                tie_breaker: "id",
+                order: SortOrder::Asc,
+                limit: 20,
+                offset: 0,
+            }
+        );
+        assert_eq!(query.sql(), " ORDER BY id ASC LIMIT ? OFFSET ?");
+    }
+
+    #[test]
+    fn test_allowed_sort_column() {
+        let query = ListParams {
+            order: Some(SortOrder::Desc),
+            ..params(Some("content"), None)
+        }
+        .validate(&TEST_CONFIG)
+        .unwrap();
+
+        assert_eq!(
+            query.sql(),
+            " ORDER BY content DESC, id DESC LIMIT ? OFFSET ?"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unlisted_sort_column() {
+        assert!(params(Some("password"), None)
+            .validate(&TEST_CONFIG)
+            .is_err());
+        assert!(params(Some("id; DROP TABLE messages"), None)
+            .validate(&TEST_CONFIG)
+            .is_err());
+        assert!(params(Some("ID"), None).validate(&TEST_CONFIG).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_limit() {
+        assert!(params(None, Some(0)).validate(&TEST_CONFIG).is_err());
+        assert!(params(None, Some(51)).validate(&TEST_CONFIG).is_err());
+        assert!(params(None, Some(50)).validate(&TEST_CONFIG).is_ok());
+    }
+
+    #[test]
+    fn test_filters() {
+        let query = ListParams {
+            filters: vec![
+                ("content".into(), "Hello".into()),
+                ("content".into(), "World".into()),
+            ],
+            ..params(None, None)
+        }
+        .validate(&TEST_CONFIG)
+        .unwrap();
+
+        assert_eq!(
+            query.sql(),
+            " WHERE content = ? AND content = ? ORDER BY id ASC LIMIT ? OFFSET ?"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unlisted_filter_column() {
+        for name in ["id", "password", "content = content OR 1", "CONTENT"] {
+            let result = ListParams {
+                filters: vec![(name.into(), "1".into())],
+                ..params(None, None)
+            }
+            .validate(&TEST_CONFIG);
+
+            assert!(result.is_err(), "filter {:?}", name);
+        }
+    }
+
+    #[test]
+    fn test_deserialize_params() {
+        let params = web::Query::<ListParams>::from_query(
+            "limit=5&order=desc&filter%5Bcontent%5D=Hello%20there&other=1",
+        )
+        .unwrap()
+        .into_inner();
+
+        assert_eq!(params.limit, Some(5));
+        assert_eq!(params.order, Some(SortOrder::Desc));
+        assert_eq!(
+            params.filters,
+            vec![("content".to_string(), "Hello there".to_string())]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_unparseable_params_rejected() {
+        let app = init_service(App::new().app_data(list_query_config()).route(
+            "/",
+            web::get().to(|_: web::Query<ListParams>| async { HttpResponse::Ok().finish() }),
+        ))
+        .await;
+
+        for uri in ["/?limit=abc", "/?order=sideways"] {
+            let req = TestRequest::get().uri(uri).to_request();
+            let res = call_service(&app, req).await;
+
+            assert_eq!(res.status(), StatusCode::BAD_REQUEST, "uri {}", uri);
+
+            let body: ErrorResponse = read_body_json(res).await;
+            assert_eq!(body.code, ErrorCode::InvalidListParams);
+        }
+    }
+
+    #[test]
+    fn test_links() {
+        let query = ListParams {
+            offset: Some(10),
+            ..params(Some("content"), Some(5))
+        }
+        .validate(&TEST_CONFIG)
+        .unwrap();
+
+        assert_eq!(
+            query.links("/messages", "sort=content&limit=5&offset=10", 5),
+            Some(
+                "</messages?sort=content&limit=5&offset=15>; rel=\"next\", \
+                 </messages?sort=content&limit=5&offset=5>; rel=\"prev\""
+                    .into()
+            )
+        );
+
+        // A page which isn't full is the last one.
+        assert_eq!(
+            query.links("/messages", "offset=10&limit=5&sort=content", 4),
+            Some("</messages?limit=5&sort=content&offset=5>; rel=\"prev\"".into())
+        );
+
+        let first = params(None, Some(5)).validate(&TEST_CONFIG).unwrap();
+        assert_eq!(
+            first.links("/messages", "limit=5", 5),
+            Some("</messages?limit=5&offset=5>; rel=\"next\"".into())
+        );
+        assert_eq!(first.links("/messages", "", 2), None);
+    }
+
+    // /messages allows no filters, so the route here uses TEST_CONFIG. The links are built from the raw query string, so
+    // an encoded filter key and any parameters the endpoint doesn't know are passed on unchanged.
+    #[actix_web::test]
+    async fn test_links_keep_encoded_params() {
+        let app = init_service(App::new().app_data(list_query_config()).route(
+            "/items",
+            web::get().to(
+                |req: HttpRequest, params: web::Query<ListParams>| async move {
+                    let query = params.validate(&TEST_CONFIG).unwrap();
+                    let links = query.links(req.path(), req.query_string(), query.limit as usize);
+
+                    HttpResponse::Ok().body(links.unwrap_or_default())
+                },
+            ),
+        ))
+        .await;
+
+        let req = TestRequest::get()
+            .uri("/items?filter%5Bcontent%5D=Hello%20there&limit=2&offset=2&other=1")
+            .to_request();
+        let res = call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        assert_eq!(
+            read_body(res).await,
+            "</items?filter%5Bcontent%5D=Hello%20there&limit=2&other=1&offset=4>; rel=\"next\", \
+             </items?filter%5Bcontent%5D=Hello%20there&limit=2&other=1&offset=0>; rel=\"prev\""
+        );
+    }
+
+    #[test]
+    fn test_map_rows_skips_failures() {
+        let rows = vec![Some(1), None, Some(3), None];
+
+        let (values, skipped) = map_rows(rows, |row| row.ok_or_else(|| "NULL value".into()));
+
+        assert_eq!(values, vec![1, 3]);
+        assert_eq!(skipped, 2);
+    }
+}
@@ -0,0 +1,193 @@
+use super::{errors::AppError, metrics::SHED_REQUESTS};
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    ResponseError,
+};
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+// Paths which are never shed, so that probes keep seeing the server as alive while it is under load.
+const EXEMPT_PATHS: [&str; 1] = ["/health-check"];
+
+// Middleware which limits the number of requests in flight across every worker. Requests over the limit are rejected
+// straight away with a 503 and Retry-After, rather than queueing until memory or database connections run out. A
+// request counts as in flight until its handler has returned a response.
+#[derive(Clone)]
+pub struct LoadShedding {
+    max_in_flight: Option<usize>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl LoadShedding {
+    // None disables the limit. The counter is shared by every clone, so this should be created once, outside the
+    // HttpServer factory.
+    pub fn new(max_in_flight: Option<usize>) -> Self {
+        LoadShedding {
+            max_in_flight,
+            in_flight: Arc::default(),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for LoadShedding
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Transform = LoadSheddingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LoadSheddingMiddleware {
+            service,
+            config: self.clone(),
+        }))
+    }
+}
+
+pub struct LoadSheddingMiddleware<S> {
+    service: S,
+    config: LoadShedding,
+}
+
+// Decrements the in-flight count when the request finishes, including when its future is dropped.
+struct InFlight(Arc<AtomicUsize>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl<S, B> Service<ServiceRequest> for LoadSheddingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let max_in_flight = match self.config.max_in_flight {
+            Some(max_in_flight) if !EXEMPT_PATHS.contains(&req.path()) => max_in_flight,
+            _ => {
+                let fut = self.service.call(req);
+                return Box::pin(async move { Ok(fut.await?.map_into_left_body()) });
+            }
+        };
+
+        let in_flight = &self.config.in_flight;
+        let already_in_flight = in_flight.fetch_add(1, Ordering::SeqCst);
+        if already_in_flight >= max_in_flight {
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            SHED_REQUESTS.increment(format_args!(
+                "Shedding {} {} with {} requests in flight (limit {})",
+                req.method(),
+                req.path(),
+                already_in_flight,
+                max_in_flight
+            ));
+
+            let res = req.into_response(AppError::Overloaded.error_response());
+            return Box::pin(async move { Ok(res.map_into_right_body()) });
+        }
+
+        let guard = InFlight(Arc::clone(in_flight));
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let res = fut.await;
+            drop(guard);
+            Ok(res?.map_into_left_body())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::errors::*,
+        util::{configuration::SecurityHeadersConfiguration, security_headers::security_headers},
+    };
+    use actix_web::{
+        http::{header, StatusCode},
+        test, web, App, HttpResponse,
+    };
+
+    #[actix_web::test]
+    async fn test_sheds_over_limit() {
+        let app = test::init_service(
+            App::new()
+                .wrap(LoadShedding::new(Some(1)))
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route("/health-check", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        // Calling the service takes the in-flight slot straight away, and it is held until the future is dropped.
+        let held = app.call(test::TestRequest::get().uri("/").to_request());
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(res.headers().contains_key(header::RETRY_AFTER));
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::Overloaded);
+
+        let req = test::TestRequest::get().uri("/health-check").to_request();
+        let res = test::call_service(&app, req).await;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        drop(held);
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn test_shed_response_has_security_headers() {
+        let app = test::init_service(
+            App::new()
+                .wrap(LoadShedding::new(Some(0)))
+                .wrap(security_headers(&SecurityHeadersConfiguration::default()))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert!(res.headers().contains_key(header::X_CONTENT_TYPE_OPTIONS));
+    }
+
+    #[actix_web::test]
+    async fn test_no_limit() {
+        let app = test::init_service(
+            App::new()
+                .wrap(LoadShedding::new(None))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let _held = app.call(test::TestRequest::get().uri("/").to_request());
+
+        let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    fmt::Arguments,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+// Requests which failed because no database connection became available in time.
+pub static DATABASE_POOL_TIMEOUTS: Counter = Counter::new("database_pool_timeouts");
+// Requests rejected by load shedding without being handled.
+pub static SHED_REQUESTS: Counter = Counter::new("shed_requests");
+
+// Counter of events since startup which should be alerted on. There is no metrics exporter, so every increment is
+// logged at warn level with the counter's name and total, e.g. `... (database_pool_timeouts: 3)`, and alerts are set
+// up on those log lines.
+pub struct Counter {
+    name: &'static str,
+    count: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str) -> Self {
+        Counter {
+            name,
+            count: AtomicU64::new(0),
+        }
+    }
+
+    // Counts one occurrence and logs it with a description, returning the new total.
+    pub fn increment(&self, description: Arguments) -> u64 {
+        let count = self.count.fetch_add(1, Ordering::Relaxed) + 1;
+        log::warn!("{} ({}: {})", description, self.name, count);
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counter() {
+        let counter = Counter::new("test_events");
+
+        assert_eq!(counter.increment(format_args!("First event")), 1);
+        assert_eq!(counter.increment(format_args!("Second event")), 2);
+    }
+}
@@ -1,6 +1,19 @@
+pub mod body_logging;
+pub mod cache_control;
 pub mod configuration;
 pub mod database;
+pub mod encoding;
 pub mod environment;
+pub mod errors;
+pub mod json;
+pub mod list;
+pub mod load_shedding;
+pub mod metrics;
+pub mod prefer;
+pub mod request_id;
+pub mod secrets;
+pub mod security_headers;
+pub mod server_timing;
 pub mod url;
 
 #[cfg(test)]
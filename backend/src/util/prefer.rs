@@ -0,0 +1,117 @@
+use actix_web::{
+    dev::Payload,
+    http::header::{HeaderName, HeaderValue},
+    FromRequest, HttpRequest, HttpResponseBuilder,
+};
+use std::future::{ready, Ready};
+
+pub const PREFER: HeaderName = HeaderName::from_static("prefer");
+pub const PREFERENCE_APPLIED: HeaderName = HeaderName::from_static("preference-applied");
+
+// Whether a create endpoint returns the created resource in the body, chosen by the client with the Prefer header
+// (RFC 7240), e.g. `Prefer: return=minimal`. Create endpoints return the representation unless the client asks for
+// minimal, and send a Location header either way.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReturnPreference {
+    Minimal,
+    Representation,
+}
+
+impl ReturnPreference {
+    // None if the client didn't state a supported return preference.
+    fn from_headers<'a>(values: impl Iterator<Item = &'a HeaderValue>) -> Option<Self> {
+        values
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            // Parameters of a preference (after the first `;`) don't apply to `return`, so they are ignored.
+            .filter_map(|preference| preference.split(';').next()?.split_once('='))
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("return"))
+            .find_map(|(_, value)| match value.trim().trim_matches('"') {
+                value if value.eq_ignore_ascii_case("minimal") => Some(ReturnPreference::Minimal),
+                value if value.eq_ignore_ascii_case("representation") => {
+                    Some(ReturnPreference::Representation)
+                }
+                _ => None,
+            })
+    }
+
+    pub fn from_request(req: &HttpRequest) -> Option<Self> {
+        ReturnPreference::from_headers(req.headers().get_all(PREFER))
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReturnPreference::Minimal => "return=minimal",
+            ReturnPreference::Representation => "return=representation",
+        }
+    }
+}
+
+// Extracts the client's return preference, along with whether they stated one, so that Preference-Applied is only sent
+// back to clients which asked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Prefer {
+    pub return_preference: ReturnPreference,
+    requested: bool,
+}
+
+impl Prefer {
+    // Sets Preference-Applied if the client stated a return preference, which is always honoured.
+    pub fn applied(&self, builder: &mut HttpResponseBuilder) {
+        if self.requested {
+            builder.insert_header((PREFERENCE_APPLIED, self.return_preference.as_str()));
+        }
+    }
+}
+
+impl FromRequest for Prefer {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let preference = ReturnPreference::from_request(req);
+
+        ready(Ok(Prefer {
+            return_preference: preference.unwrap_or(ReturnPreference::Representation),
+            requested: preference.is_some(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test;
+
+    fn preference_for(prefer: &[&str]) -> Option<ReturnPreference> {
+        let mut req = test::TestRequest::default();
+
+        for value in prefer {
+            req = req.append_header((PREFER, *value));
+        }
+
+        ReturnPreference::from_request(&req.to_http_request())
+    }
+
+    #[actix_web::test]
+    async fn test_return_preference() {
+        assert_eq!(preference_for(&[]), None);
+        assert_eq!(
+            preference_for(&["return=minimal"]),
+            Some(ReturnPreference::Minimal)
+        );
+        assert_eq!(
+            preference_for(&["return=representation"]),
+            Some(ReturnPreference::Representation)
+        );
+        assert_eq!(
+            preference_for(&["respond-async, RETURN = \"minimal\"; foo=bar"]),
+            Some(ReturnPreference::Minimal)
+        );
+        assert_eq!(
+            preference_for(&["wait=10", "return=minimal"]),
+            Some(ReturnPreference::Minimal)
+        );
+        assert_eq!(preference_for(&["return=everything"]), None);
+    }
+}
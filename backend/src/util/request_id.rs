@@ -0,0 +1,190 @@
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    HttpMessage,
+};
+use std::{
+    fmt::Display,
+    future::{ready, Future, Ready},
+    pin::Pin,
+};
+use uuid::Uuid;
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+// Longest request id accepted from a client. Longer ids are replaced with a generated one.
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+// Id of a request, stored in the request extensions. It is taken from the X-Request-Id header when the client (or a
+// proxy in front of the server) sends a valid one, and is otherwise a generated UUID.
+#[derive(Clone)]
+pub struct RequestId(String);
+
+impl RequestId {
+    fn from_header(value: Option<&HeaderValue>) -> Self {
+        let valid = value.and_then(|value| value.to_str().ok()).filter(|value| {
+            !value.is_empty()
+                && value.len() <= MAX_REQUEST_ID_LENGTH
+                && value
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        });
+
+        match valid {
+            Some(value) => RequestId(value.into()),
+            None => RequestId(Uuid::new_v4().to_string()),
+        }
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+// Middleware which gives every request an id and returns it in the X-Request-Id header of every response, including
+// errors. Server errors are logged with the id, so a quoted id can be found in the logs.
+pub struct RequestIds;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIds
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = RequestIdsMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdsMiddleware { service }))
+    }
+}
+
+pub struct RequestIdsMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = RequestId::from_header(req.headers().get(X_REQUEST_ID));
+        req.extensions_mut().insert(request_id.clone());
+
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+
+            if res.status().is_server_error() {
+                log::error!(
+                    "{} {} failed with {} (request id {})",
+                    res.request().method(),
+                    res.request().path(),
+                    res.status(),
+                    request_id
+                );
+            }
+
+            // The id only contains characters which are valid in a header value.
+            if let Ok(value) = HeaderValue::from_str(&request_id.0) {
+                res.headers_mut().insert(X_REQUEST_ID, value);
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::errors::*, routes::fallback::default_service, util::encoding::ErrorEncoding,
+    };
+    use actix_web::{http::StatusCode, test, web, App};
+
+    #[actix_web::test]
+    async fn test_request_id_from_header() {
+        let valid = HeaderValue::from_static("abc-123_DEF.4");
+        assert_eq!(
+            RequestId::from_header(Some(&valid)).to_string(),
+            "abc-123_DEF.4"
+        );
+
+        let invalid = HeaderValue::from_static("abc 123");
+        assert_ne!(
+            RequestId::from_header(Some(&invalid)).to_string(),
+            "abc 123"
+        );
+
+        let too_long = HeaderValue::from_str(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1)).unwrap();
+        assert!(RequestId::from_header(Some(&too_long)).to_string().len() <= MAX_REQUEST_ID_LENGTH);
+
+        assert!(Uuid::parse_str(&RequestId::from_header(None).to_string()).is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_error_response_has_request_id() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ErrorEncoding)
+                .wrap(RequestIds)
+                .default_service(web::to(default_service)),
+        )
+        .await;
+
+        let req = test::TestRequest::put().uri("/messages").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+        let header = res
+            .headers()
+            .get(X_REQUEST_ID)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.request_id, Some(header));
+    }
+
+    #[actix_web::test]
+    async fn test_request_id_on_not_found() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ErrorEncoding)
+                .wrap(RequestIds)
+                .default_service(web::to(default_service)),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/unknown")
+            .insert_header((X_REQUEST_ID, "client-id"))
+            .to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+        assert_eq!(res.headers().get(X_REQUEST_ID).unwrap(), "client-id");
+
+        let body: ErrorResponse = test::read_body_json(res).await;
+        assert_eq!(body.code, ErrorCode::NotFound);
+        assert_eq!(body.request_id.as_deref(), Some("client-id"));
+    }
+}
@@ -0,0 +1,58 @@
+use super::configuration::SecurityHeadersConfiguration;
+use actix_web::{
+    http::header::{
+        CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+        X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    },
+    middleware::DefaultHeaders,
+};
+
+// Builds the middleware which adds the configured security headers to every response. Headers which are disabled in
+// the configuration are skipped, and any header already set by a handler is left untouched.
+pub fn security_headers(config: &SecurityHeadersConfiguration) -> DefaultHeaders {
+    let headers = [
+        (STRICT_TRANSPORT_SECURITY, &config.strict_transport_security),
+        (X_CONTENT_TYPE_OPTIONS, &config.content_type_options),
+        (X_FRAME_OPTIONS, &config.frame_options),
+        (CONTENT_SECURITY_POLICY, &config.content_security_policy),
+        (REFERRER_POLICY, &config.referrer_policy),
+    ];
+
+    headers
+        .into_iter()
+        .filter_map(|(name, value)| value.clone().map(|value| (name, value)))
+        .fold(DefaultHeaders::new(), DefaultHeaders::add)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_security_headers() {
+        let config = SecurityHeadersConfiguration {
+            strict_transport_security: None,
+            ..SecurityHeadersConfiguration::default()
+        };
+
+        let app = test::init_service(
+            App::new()
+                .wrap(security_headers(&config))
+                .route("/", web::get().to(HttpResponse::Ok)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert!(!res.headers().contains_key(STRICT_TRANSPORT_SECURITY));
+        assert_eq!(
+            res.headers().get(X_CONTENT_TYPE_OPTIONS).unwrap(),
+            "nosniff"
+        );
+        assert_eq!(res.headers().get(X_FRAME_OPTIONS).unwrap(), "DENY");
+        assert!(res.headers().contains_key(CONTENT_SECURITY_POLICY));
+        assert!(res.headers().contains_key(REFERRER_POLICY));
+    }
+}
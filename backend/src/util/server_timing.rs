@@ -0,0 +1,185 @@
+use actix_web::{
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    FromRequest, HttpMessage, HttpRequest,
+};
+use std::{
+    cell::RefCell,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+type TimingEntries = Rc<RefCell<Vec<(&'static str, Duration)>>>;
+
+// Request scoped collector of timings, rendered into the Server-Timing header once the response is ready. Handlers
+// extract it and wrap the calls they want measured (e.g. database queries) with `time`. When Server-Timing is
+// disabled the collector is empty and timings aren't recorded.
+#[derive(Clone, Default)]
+pub struct Timings(Option<TimingEntries>);
+
+impl Timings {
+    pub async fn time<F: Future>(&self, name: &'static str, fut: F) -> F::Output {
+        let start = Instant::now();
+        let output = fut.await;
+
+        if let Some(entries) = &self.0 {
+            entries.borrow_mut().push((name, start.elapsed()));
+        }
+
+        output
+    }
+
+    // Renders the timings as a Server-Timing header value, with the time for each name summed across all calls, e.g.
+    // `db;dur=1.234, total;dur=2.345`.
+    fn header_value(&self, total: Duration) -> Option<HeaderValue> {
+        let entries = self.0.as_ref()?.borrow();
+
+        let mut totals: Vec<(&str, Duration)> = vec![];
+        for (name, duration) in entries.iter() {
+            match totals.iter_mut().find(|(existing, _)| existing == name) {
+                Some((_, sum)) => *sum += *duration,
+                None => totals.push((name, *duration)),
+            }
+        }
+        totals.push(("total", total));
+
+        let value = totals
+            .iter()
+            .map(|(name, duration)| format!("{};dur={:.3}", name, duration.as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        HeaderValue::from_str(&value).ok()
+    }
+}
+
+impl FromRequest for Timings {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req
+            .extensions()
+            .get::<Timings>()
+            .cloned()
+            .unwrap_or_default()))
+    }
+}
+
+// Middleware which adds the Server-Timing header to responses. This exposes internal timings, so it is only enabled
+// through configuration.
+#[derive(Clone, Copy)]
+pub struct ServerTiming {
+    pub enabled: bool,
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ServerTiming
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = ServerTimingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ServerTimingMiddleware {
+            service,
+            enabled: self.enabled,
+        }))
+    }
+}
+
+pub struct ServerTimingMiddleware<S> {
+    service: S,
+    enabled: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for ServerTimingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        if !self.enabled {
+            return Box::pin(self.service.call(req));
+        }
+
+        let start = Instant::now();
+        let timings = Timings(Some(Rc::default()));
+        req.extensions_mut().insert(timings.clone());
+
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut res = fut.await?;
+
+            if let Some(value) = timings.header_value(start.elapsed()) {
+                res.headers_mut().insert(SERVER_TIMING, value);
+            }
+
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, web, App, HttpResponse};
+
+    async fn timed(timings: Timings) -> HttpResponse {
+        timings.time("db", async {}).await;
+        timings.time("db", async {}).await;
+        HttpResponse::Ok().finish()
+    }
+
+    #[actix_web::test]
+    async fn test_server_timing_enabled() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ServerTiming { enabled: true })
+                .route("/", web::get().to(timed)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+
+        let header = res.headers().get(SERVER_TIMING).unwrap().to_str().unwrap();
+        let names: Vec<&str> = header
+            .split(", ")
+            .map(|entry| entry.split(';').next().unwrap())
+            .collect();
+        assert_eq!(names, vec!["db", "total"]);
+    }
+
+    #[actix_web::test]
+    async fn test_server_timing_disabled() {
+        let app = test::init_service(
+            App::new()
+                .wrap(ServerTiming { enabled: false })
+                .route("/", web::get().to(timed)),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/").to_request();
+        let res = test::call_service(&app, req).await;
+
+        assert!(!res.headers().contains_key(SERVER_TIMING));
+    }
+}
@@ -1,5 +1,13 @@
-use crate::Error;
-use sqlx::{mysql::MySqlPoolOptions, MySqlPool};
+use crate::{
+    models::messages::Message,
+    util::database::{new_id, RetryConfig},
+    AppState, Error,
+};
+use sqlx::{
+    mysql::{MySql, MySqlArguments, MySqlPoolOptions},
+    query::Query,
+    MySqlPool, Row,
+};
 
 pub struct TestPool {
     pub pool: MySqlPool,
@@ -22,6 +30,77 @@ impl TestPool {
     pub fn get(&self) -> MySqlPool {
         self.pool.clone()
     }
+
+    // Builds an AppState using the test connection, with the default values for everything else.
+    pub fn app_state(&self) -> AppState {
+        AppState {
+            pool: self.get(),
+            db_retry: RetryConfig::default(),
+        }
+    }
+}
+
+// Initialises the app for a route test, with the given state and the routes from a ServiceConfig function, e.g.
+// `test_app!(pool.app_state(), |cfg| { cfg.service(health_check); })`. This is a macro as the service type returned by
+// `init_service` can't be named.
+macro_rules! test_app {
+    ($app_state:expr, $config:expr) => {
+        actix_web::test::init_service(
+            actix_web::App::new()
+                .app_data(actix_web::web::Data::new($app_state))
+                .configure($config),
+        )
+        .await
+    };
+}
+
+pub(crate) use test_app;
+
+// One row of EXPLAIN output for a query, i.e. how one table is accessed.
+pub struct QueryPlan {
+    pub table: Option<String>,
+    pub access_type: Option<String>,
+    pub key: Option<String>,
+}
+
+impl TestPool {
+    // Runs EXPLAIN for a query, which must start with EXPLAIN and have its parameters bound.
+    pub async fn explain(
+        &self,
+        query: Query<'_, MySql, MySqlArguments>,
+    ) -> Result<Vec<QueryPlan>, Error> {
+        let rows = query.fetch_all(&self.pool).await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(QueryPlan {
+                    table: row.try_get("table")?,
+                    access_type: row.try_get("type")?,
+                    key: row.try_get("key")?,
+                })
+            })
+            .collect()
+    }
+}
+
+// Asserts that no table in a query plan is read with a full table scan, so the query stays fast as the table grows. The
+// query is only used in the failure message.
+pub fn assert_uses_index(plans: &[QueryPlan], query: &str) {
+    for plan in plans {
+        assert_ne!(
+            plan.access_type.as_deref(),
+            Some("ALL"),
+            "full table scan of {:?} in {}",
+            plan.table,
+            query
+        );
+        assert!(
+            plan.key.is_some(),
+            "no index used for {:?} in {}",
+            plan.table,
+            query
+        );
+    }
 }
 
 impl Drop for TestPool {
@@ -32,3 +111,41 @@ impl Drop for TestPool {
         });
     }
 }
+
+// Builder for seeding a message into the test database. Any value not set is generated. The id is generated on insert
+// in the same way as add_message, so seeded messages are ordered by when they were inserted.
+pub struct TestMessage {
+    pub content: String,
+}
+
+impl Default for TestMessage {
+    fn default() -> Self {
+        TestMessage {
+            content: "Test message".into(),
+        }
+    }
+}
+
+impl TestMessage {
+    pub fn content(mut self, content: &str) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    pub async fn insert(self, pool: &TestPool) -> Result<Message, Error> {
+        let id = new_id(&pool.pool).await?;
+
+        sqlx::query!(
+            "INSERT INTO messages (id, content) VALUES (UUID_TO_BIN(?, true), ?)",
+            id,
+            self.content
+        )
+        .execute(&pool.pool)
+        .await?;
+
+        Ok(Message {
+            id,
+            content: self.content,
+        })
+    }
+}
@@ -9,11 +9,30 @@ pub struct Url {
     pub path: RouteCollection,
 }
 
+impl Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}://{}:{}/{}",
+            self.protocol, self.host, self.port, self.path
+        )
+    }
+}
+
 pub enum UrlProtocol {
     Http,
     Https,
 }
 
+impl Display for UrlProtocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UrlProtocol::Http => "http",
+            UrlProtocol::Https => "https",
+        })
+    }
+}
+
 impl TryFrom<&str> for UrlProtocol {
     type Error = Error;
 